@@ -6,18 +6,37 @@
 // wires (P1|P2->P0). Main wires (P0<->P0) are then stored in a separate vector, called 'acts'
 // (active wires), and aux wires (P1|P2->P1|P2) are represented by VAR pointers. The 'acts' vector
 // is automatically updated during reduction, which allows us to always keep track of all active
-// wires. Pointers contain the tag of the pointed object. This allows for 1. unboxed ERAs, NUMs,
-// REFs; 2. omitting labels on nodes (as these are stored on their parent's pointers). This file
-// also includes REF pointers, which expand to pre-defined modules (closed nets with 1 free wire).
-// This expansion is performed on demand, and ERA-REF pointers are collected, allowing the runtime
-// to compute tail-recursive functions with constant memory usage.
+// wires. Pointers contain the tag of the pointed object. This allows for unboxed ERAs, NUMs and
+// REFs. Labels of constructors are stored on their nodes, next to the aux ports, so that pointers
+// keep their whole value for the location. This file also includes REF pointers, which expand to
+// pre-defined modules (closed nets with 1 free wire). This expansion is performed on demand, and
+// ERA-REF pointers are collected, allowing the runtime to compute tail-recursive functions with
+// constant memory usage.
 
-use std::{collections::HashMap, fmt::Debug};
+use std::{collections::{BTreeMap, HashMap}, fmt::Debug};
+use std::time::{Duration, Instant};
 
 use crate::readback_lnet;
 
 pub type Tag = u16;
+
+// A pointer is a tag and a value, packed in a single word. By default, it has 32 bits: a 4-bit tag
+// and a 28-bit value. With the 'ptr64' feature, it has 64 bits: an 8-bit tag and a 56-bit value,
+// which lifts the limits on heap size, numbers and reference ids.
+#[cfg(not(feature = "ptr64"))]
 pub type Val = u32;
+#[cfg(feature = "ptr64")]
+pub type Val = u64;
+
+#[cfg(not(feature = "ptr64"))]
+pub const TAG_BITS: u32 = 4;
+#[cfg(feature = "ptr64")]
+pub const TAG_BITS: u32 = 8;
+
+pub const VAL_BITS: u32 = Val::BITS - TAG_BITS; // bits on the value of a pointer
+pub const VAL_MASK: Val = (1 << VAL_BITS) - 1;
+pub const NUM_BITS: u32 = VAL_BITS - 4; // bits on the value of a number, below its operator
+pub const NUM_MASK: Val = (1 << NUM_BITS) - 1;
 
 // Core terms
 pub const NIL: Tag = 0x0; // empty node
@@ -29,9 +48,42 @@ pub const VR2: Tag = 0x5; // variable pointing to aux2 port of node
 pub const RDR: Tag = 0x6; // redirection to root
 pub const RD1: Tag = 0x7; // redirection to aux1 port of node
 pub const RD2: Tag = 0x8; // redirection to aux2 port of node
-pub const NUM: Tag = 0x9; // redirection to aux2 port of node
-pub const CON: Tag = 0xA; // points to main port of con node
-pub const DUP: Tag = 0xB; // points to main port of dup node; higher labels also dups
+pub const NUM: Tag = 0x9; // unboxed number
+pub const CTR: Tag = 0xA; // points to main port of constructor node, labelled on the node
+pub const OP2: Tag = 0xB; // points to main port of numeric operation, waiting for left operand
+pub const OP1: Tag = 0xC; // points to main port of numeric operation, waiting for right operand
+pub const MAT: Tag = 0xD; // points to main port of numeric match, branching on zero or successor
+
+// Constructors have a label, stored on their node, so that pointers keep their whole value for the
+// location. Constructors with the same label annihilate, while others commute. A label is as wide
+// as a pointer: there are 2^32 labels, or 2^64 with 'ptr64'. This makes nodes a half larger than
+// their two ports, but a narrower label wouldn't shrink them, as nodes are padded to the alignment
+// of their pointers, and it lets the parallel reducer access labels as it accesses ports.
+pub type Lab = Val;
+pub const CON: Lab = 0; // label of con nodes
+pub const DUP: Lab = 1; // label of dup nodes; higher labels also dups
+
+// Numeric operators. A number has a NUM_BITS value and a 4-bit operator, stored on its top bits, so
+// values have 24 bits, or 52 with 'ptr64', and results wrap around at that width. When two numbers
+// meet on an operation node, the operator of the right one (or, if it has none, of the left one) is
+// applied to both values. An OPR number is an operator alone: applying it gives the other number
+// its value as operator, which allows operating on two computed numbers.
+pub const USE: Val = 0x0; // no operator
+pub const ADD: Val = 0x1; // addition
+pub const SUB: Val = 0x2; // subtraction
+pub const MUL: Val = 0x3; // multiplication
+pub const DIV: Val = 0x4; // division, zero when dividing by zero
+pub const MOD: Val = 0x5; // remainder, zero when dividing by zero
+pub const EQ : Val = 0x6; // equal
+pub const NE : Val = 0x7; // not equal
+pub const LT : Val = 0x8; // less than
+pub const GT : Val = 0x9; // greater than
+pub const AND: Val = 0xA; // bitwise and
+pub const OR : Val = 0xB; // bitwise or
+pub const XOR: Val = 0xC; // bitwise xor
+pub const SHL: Val = 0xD; // shift left
+pub const SHR: Val = 0xE; // shift right
+pub const OPR: Val = 0xF; // operator alone
 
 // A node port: 1 or 2. Main ports are omitted.
 pub type Port = usize;
@@ -40,14 +92,18 @@ pub const P2 : Port = 1;
 
 // A tagged pointer. When tag >= VR1, it stores an absolute target location (node index).
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(transparent)]
 pub struct Ptr {
   pub data: Val,
 }
 
-// A node is just a pair of two delta pointers. It uses 64 bits.
+// A node is a pair of two delta pointers, and the label of a constructor, which is 0 on other
+// nodes. It uses 96 bits, or 192 bits with 'ptr64', a half more than its ports alone (see 'Lab').
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[repr(C)]
 pub struct Node {
   pub ports: [Ptr; 2],
+  pub lab: Lab,
 }
 
 // A net has:
@@ -56,7 +112,10 @@ pub struct Node {
 // - node: a vector of nodes, with main ports omitted.
 // - used: total nodes currently allocated on the graph.
 // - rwts: total graph rewrites performed inside this net.
-// - next: next pointer to allocate memory (internal).
+// - stats: rewrites performed inside this net, by rule.
+// - limit: maximum length 'node' can grow to.
+// - prof: costs of each expanded definition, if enabled with 'profile'.
+// - page: the allocator state (internal).
 #[derive(Clone, PartialEq, Eq)]
 pub struct Net {
   pub root: Ptr,
@@ -64,35 +123,361 @@ pub struct Net {
   pub node: Vec<Node>,
   pub used: usize,
   pub rwts: usize,
-      next: usize,
-      locs: Vec<u32>,
+  pub stats: Stats,
+  pub limit: usize,
+  pub prof: Option<Box<Profile>>,
+      page: Pages,
+}
+
+// Statistics of a reduction. Each rewrite counts as one of:
+// - anni: annihilations, of constructors with the same label (CON-CON).
+// - comm: commutations, of nodes of different kinds (CON-DUP).
+// - eras: erasures of a node (ERA-CTR).
+// - copy: copies of a number by a constructor (CTR-NUM).
+// - oper: numeric operations and matches (OP2-NUM, OP1-NUM, MAT-NUM).
+// - coll: REFs collected by an eraser, without being expanded (REF-ERA).
+// - link: redexes with a variable, left by REFs that expand to one.
+// - void: pairs that just vanish, such as ERA-ERA.
+// Besides, it records:
+// - dref: REFs expanded into their definitions, by a rewrite or by 'expand'.
+// - peak: most nodes allocated at once. Parallel reductions only sample it between runs.
+// - rnds: 'reduce' rounds, or runs of a depth-first or parallel reducer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+  pub anni: usize,
+  pub comm: usize,
+  pub eras: usize,
+  pub copy: usize,
+  pub oper: usize,
+  pub coll: usize,
+  pub link: usize,
+  pub void: usize,
+  pub dref: usize,
+  pub peak: usize,
+  pub rnds: usize,
+}
+
+// A profile of the definitions expanded by a reduction. Each node is owned by the definition that
+// loaded it, and nodes created by a rewrite are owned by the one blamed for that rewrite. Rewrites
+// are blamed on the owner of the nodes they consume, or on the REF they expand. Only sequential
+// reducers record it. It has:
+// - defs: the costs of each definition, by id.
+// - owns: the owner of each node of the heap.
+// - curr: the owner of the rewrite being performed, or NO_DEF.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+  pub defs: BTreeMap<Val, Cost>,
+      owns: Vec<Val>,
+      curr: Val,
+}
+
+// The cost of a definition:
+// - dref: times it was expanded.
+// - node: nodes allocated by its expansions.
+// - acts: redexes injected by its expansions.
+// - rwts: rewrites blamed on it, which includes those on nodes created by earlier ones.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cost {
+  pub dref: usize,
+  pub node: usize,
+  pub acts: usize,
+  pub rwts: usize,
+}
+
+// Owner of the nodes that no definition loaded, such as those of a net built by hand.
+const NO_DEF: Val = Val::MAX;
+
+// A worker has the scratch space used to reduce a net, which isn't part of the net itself:
+// - locs: where each node of the definition being loaded was allocated, used by deref.
+// - owns: the redex whose trees hold each node, used by lazy reductions.
+// - obs: the observer told about each step of the reduction, or '()' for none.
+pub struct Worker<O: ReductionObserver = ()> {
+  pub locs: Vec<Val>,
+  pub owns: Vec<usize>,
+  pub obs: O,
+}
+
+// The rules a rewrite can apply, each counted by the field of 'Stats' with the same name.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Rule {
+  Anni,
+  Comm,
+  Eras,
+  Copy,
+  Oper,
+  Coll,
+  Link,
+  Void,
+}
+
+// A rewrite, as told to an observer:
+// - rule: the rule it applied.
+// - a, b: the redex it reduced, before its REFs were expanded.
+// - alloc: nodes it allocated, including those of expanded REFs.
+// - freed: nodes it freed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Rewrite {
+  pub rule: Rule,
+  pub a: Ptr,
+  pub b: Ptr,
+  pub alloc: usize,
+  pub freed: usize,
+}
+
+// Hooks called while a net is reduced, to trace or debug it. Every hook does nothing by default.
+// Workers without an observer use '()', for which the calls compile to nothing.
+pub trait ReductionObserver {
+  // Called after each rewrite.
+  #[inline(always)]
+  fn rewrite(&mut self, _rw: &Rewrite) {}
+
+  // Called when a REF to the definition 'id' is expanded, loading 'node' nodes.
+  #[inline(always)]
+  fn deref(&mut self, _id: Val, _node: usize) {}
+
+  // Called when two pointers are linked by a rewrite, forming a wire, or a redex if both are
+  // principal.
+  #[inline(always)]
+  fn link(&mut self, _a: Ptr, _b: Ptr) {}
+
+  // Called as each 'reduce' round starts, with its number and the redexes it will reduce.
+  #[inline(always)]
+  fn round(&mut self, _rnds: usize, _acts: usize) {}
+}
+
+impl ReductionObserver for () {}
+
+// Limits on a reduction. A reduction that reaches one stops early, keeping the pending redexes on
+// 'acts', so it can be resumed with a larger budget. A budget has:
+// - rwts: most rewrites the net may have performed, counting those of earlier reductions.
+// - node: most nodes allocated at once, on top of the net's own 'limit'.
+// - time: most wall-clock time the reduction may take, read every TICK rewrites or so, so it may
+//   be exceeded by the time those take.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Budget {
+  pub rwts: Option<usize>,
+  pub node: Option<usize>,
+  pub time: Option<Duration>,
+}
+
+// A budget being spent, by a reduction that started at some instant:
+// - max_step: the rewrite count at which it must stop.
+// - deadline: the instant at which it must stop, if any.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Meter {
+  pub max_step: usize,
+  pub deadline: Option<Instant>,
+}
+
+// How a reduction ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+  Normal,      // no redex is left
+  OutOfFuel,   // the rewrite budget was spent
+  OutOfMemory, // an interaction needed more nodes than the budget or 'limit' allows
+  Timeout,     // the time budget was spent
 }
 
+// How far a reduction run in slices got.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Progress {
+  Pending, // there are redexes left, for the next slice
+  Done,    // no redex is left
+}
+
+// Rewrites between reads of the clock, which is slower than checking the rewrite count. Rounds of
+// 'reduce' only read it between them.
+pub(crate) const TICK: usize = 4096;
+
+// Number of nodes on each allocation page.
+const PAGE: usize = 4096;
+
+// Nodes are allocated by bumping an index through a page, so they are laid out in the order they
+// were created. Freed nodes are only reused once their whole page is empty, which then goes to a
+// free list. This keeps 'alloc' and 'free' O(1). Only when there are no empty pages left, pages
+// with some empty nodes are searched for, and their empty nodes are reused one by one. Pages are
+// large, so that nodes created together stay close, which matters more than reusing memory early.
+// The allocator has:
+// - next: next node to allocate, on the current page.
+// - stop: end of the current page.
+// - curr: index of the current page, if any.
+// - full: whether the current page may have allocated nodes after 'next'.
+// - more: first page that was never used.
+// - scan: where to start searching for partially empty pages.
+// - live: allocated nodes on each page.
+// - free: empty pages, other than the current one.
+#[derive(Clone, PartialEq, Eq)]
+struct Pages {
+  next: usize,
+  stop: usize,
+  curr: Option<usize>,
+  full: bool,
+  more: usize,
+  scan: usize,
+  live: Vec<u32>,
+  free: Vec<usize>,
+}
+
+impl std::fmt::Display for RuntimeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RuntimeError::OutOfMemory { used, limit } => {
+        write!(f, "out of memory: {} of {} nodes in use", used, limit)
+      }
+      RuntimeError::BadPointer { ptr } => {
+        write!(f, "bad pointer: {:?} points to no node", ptr)
+      }
+      RuntimeError::BadTag { ptr } => {
+        write!(f, "bad tag: {:?} can't be where it was found", ptr)
+      }
+      RuntimeError::MissingDef { id } => {
+        write!(f, "missing definition: @#{}", id)
+      }
+      RuntimeError::AliasCycle { id } => {
+        write!(f, "alias cycle: @#{} expands to itself forever", id)
+      }
+    }
+  }
+}
+
+impl std::error::Error for RuntimeError {}
+
 impl Debug for Net {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-      Debug::fmt(&readback_lnet(self), f)
+      Debug::fmt(&readback_lnet(self, &Symbols::default()), f)
     }
 }
 
-// A book is just a map of definitions, mapping ids to closed nets.
+// Errors that stop a reduction. Running out of memory leaves the net in a valid state, with the
+// pending redexes in 'acts', so it can be inspected or reduced further. The other errors mean that
+// the net or the book is malformed. Bad pointers and tags are only found with the 'checked'
+// feature; otherwise, they are undefined behavior.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RuntimeError {
+  // An interaction needed more nodes than the net's 'limit'.
+  OutOfMemory { used: usize, limit: usize },
+  // A pointer to a node or port that is outside the heap or definition, or empty.
+  BadPointer { ptr: Ptr },
+  // A pointer with a tag that can't be where it was found, such as a variable on a redex.
+  BadTag { ptr: Ptr },
+  // A REF to a definition that isn't on the book.
+  MissingDef { id: Val },
+  // A REF to a definition whose root is a REF, and so on, forever. 'id' is on the cycle.
+  AliasCycle { id: Val },
+}
+
+// A book is just a map of definitions, indexed by id, plus the names of those ids. As ids are
+// dense, a definition is found by indexing a vector, which is what each REF expansion does.
 pub struct Book {
-  pub defs: HashMap<u32, Net, std::hash::BuildHasherDefault<nohash::NoHashHasher<u32>>>,
+  pub defs: Vec<Option<Def>>,
+  pub syms: Symbols,
+}
+
+// A definition: a closed net, keeping only what its expansion reads. It has:
+// - root: the pointer that replaces the expanded REF.
+// - acts: the redexes it adds.
+// - node: its nodes, which its pointers locate from 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Def {
+  pub root: Ptr,
+  pub acts: Vec<(Ptr, Ptr)>,
+  pub node: Vec<Node>,
+}
+
+// A symbol table, interning the names of definitions to dense ids, which are the values of their
+// REF pointers. Ids are handed out counting up from 0, and the name of each is kept for readback
+// and diagnostics. It has:
+// - names: the name of each id, if it has one.
+// - ids: the id of each name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Symbols {
+  names: Vec<Option<String>>,
+  ids: HashMap<String, Val>,
+}
+
+// A reason a name couldn't be given an id.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SymbolError {
+  // A name that can't be written after '@': it is empty, or has a character other than a letter,
+  // a digit, '_' or '.'.
+  Invalid { name: String },
+  // A name bound to an id when either already has another.
+  Collision { name: String, id: Val },
+  // There are no ids left that a REF can hold.
+  Full,
 }
 
 impl Ptr {
   #[inline(always)]
-  pub fn new(tag: Tag, val: Val) -> Self {
-    Ptr { data: (((tag as u32) << 28) | (val & 0xFFF_FFFF)) }
+  pub const fn new(tag: Tag, val: Val) -> Self {
+    Ptr { data: (((tag as Val) << VAL_BITS) | (val & VAL_MASK)) }
   }
 
   #[inline(always)]
   pub fn tag(&self) -> Tag {
-    (self.data >> 28) as Tag
+    (self.data >> VAL_BITS) as Tag
   }
 
   #[inline(always)]
   pub fn val(&self) -> Val {
-    (self.data & 0xFFF_FFFF) as Val
+    self.data & VAL_MASK
+  }
+
+  // Gets the location of a pointer to a node or port.
+  #[inline(always)]
+  pub fn loc(&self) -> Val {
+    self.val()
+  }
+
+  // Creates a number with given operator.
+  #[inline(always)]
+  pub const fn new_num(opr: Val, val: Val) -> Self {
+    Ptr::new(NUM, (opr << NUM_BITS) | (val & NUM_MASK))
+  }
+
+  // Gets the operator of a number.
+  #[inline(always)]
+  pub fn opr(&self) -> Val {
+    (self.data >> NUM_BITS) & 0xF
+  }
+
+  // Gets the value of a number, without its operator.
+  #[inline(always)]
+  pub fn num(&self) -> Val {
+    self.data & NUM_MASK
+  }
+
+  // Applies a numeric operation, 'self' being the left operand. Shift amounts are cast, as 'Val'
+  // is wider with 'ptr64'.
+  #[allow(clippy::unnecessary_cast)]
+  pub fn operate(&self, rgt: Ptr) -> Ptr {
+    let a = self.num();
+    let b = rgt.num();
+    if rgt.opr() == OPR {
+      return Ptr::new_num(b & 0xF, a);
+    }
+    if self.opr() == OPR {
+      return Ptr::new_num(a & 0xF, b);
+    }
+    let opr = if rgt.opr() != USE { rgt.opr() } else { self.opr() };
+    let val = match opr {
+      ADD => a.wrapping_add(b),
+      SUB => a.wrapping_sub(b),
+      MUL => a.wrapping_mul(b),
+      DIV => a.checked_div(b).unwrap_or(0),
+      MOD => a.checked_rem(b).unwrap_or(0),
+      EQ  => (a == b) as Val,
+      NE  => (a != b) as Val,
+      LT  => (a < b) as Val,
+      GT  => (a > b) as Val,
+      AND => a & b,
+      OR  => a | b,
+      XOR => a ^ b,
+      SHL => a.wrapping_shl(b as u32),
+      SHR => a.wrapping_shr(b as u32),
+      _   => a,
+    };
+    return Ptr::new_num(USE, val);
   }
 
   #[inline(always)]
@@ -105,6 +490,12 @@ impl Ptr {
     return self.tag() >= RDR && self.tag() <= RD2;
   }
 
+  // Transforms a variable into a redirection. Other pointers are kept as is.
+  #[inline(always)]
+  pub fn redirect(&self) -> Ptr {
+    return Ptr::new(self.tag() + if self.is_var() { 3 } else { 0 }, self.val());
+  }
+
   #[inline(always)]
   pub fn is_era(&self) -> bool {
     return self.tag() == ERA;
@@ -117,7 +508,23 @@ impl Ptr {
 
   #[inline(always)]
   pub fn is_ctr(&self) -> bool {
-    return self.tag() >= CON;
+    return self.tag() == CTR;
+  }
+
+  #[inline(always)]
+  pub fn is_op(&self) -> bool {
+    return self.tag() == OP2 || self.tag() == OP1;
+  }
+
+  #[inline(always)]
+  pub fn is_mat(&self) -> bool {
+    return self.tag() == MAT;
+  }
+
+  // Checks if this points to the main port of a node.
+  #[inline(always)]
+  pub fn is_nod(&self) -> bool {
+    return self.is_ctr() || self.is_op() || self.is_mat();
   }
 
   #[inline(always)]
@@ -128,24 +535,24 @@ impl Ptr {
   #[inline(always)]
   pub fn is_pri(&self) -> bool {
     return self.is_era()
-        || self.is_ctr()
+        || self.is_nod()
         || self.is_num()
         || self.is_ref();
   }
 
   #[inline(always)]
   pub fn has_loc(&self) -> bool {
-    return self.is_ctr()
+    return self.is_nod()
         || self.is_var() && self.tag() != VRR
         || self.is_red() && self.tag() != RDR;
   }
 
   #[inline(always)]
-  pub fn target<'a>(&'a self, net: &'a mut Net) -> Option<&mut Ptr> {
+  pub fn target<'a>(&'a self, net: &'a mut Net) -> Option<&'a mut Ptr> {
     match self.tag() {
       VRR => { Some(&mut net.root) }
-      VR1 => { Some(net.at_mut(self.val()).port_mut(P1)) }
-      VR2 => { Some(net.at_mut(self.val()).port_mut(P2)) }
+      VR1 => { Some(net.at_mut(self.loc()).port_mut(P1)) }
+      VR2 => { Some(net.at_mut(self.loc()).port_mut(P2)) }
       _   => { None }
     }
   }
@@ -170,63 +577,429 @@ impl Ptr {
   //}
 
   #[inline(always)]
-  pub fn adjust(&self, locs: &[u32]) -> Ptr {
-    unsafe {
-      return Ptr::new(self.tag(), if self.has_loc() { *locs.get_unchecked(self.val() as usize) } else { self.val() });
+  pub fn adjust(&self, locs: &[Val]) -> Ptr {
+    if self.has_loc() {
+      #[cfg(feature = "checked")]
+      let loc = locs[self.loc() as usize];
+      #[cfg(not(feature = "checked"))]
+      let loc = unsafe { *locs.get_unchecked(self.loc() as usize) };
+      return Ptr::new(self.tag(), loc);
+    } else {
+      return *self;
     }
   }
 }
 
 impl Node {
   #[inline(always)]
-  pub fn new(p1: Ptr, p2: Ptr) -> Self {
-    Node { ports: [p1, p2] }
+  pub fn new(p1: Ptr, p2: Ptr, lab: Lab) -> Self {
+    Node { ports: [p1, p2], lab }
   }
 
   #[inline(always)]
   pub fn nil() -> Self {
-    Self::new(Ptr::new(NIL,0), Ptr::new(NIL,0))
+    Self::new(Ptr::new(NIL,0), Ptr::new(NIL,0), 0)
   }
 
   #[inline(always)]
   pub fn port(&self, port: Port) -> &Ptr {
+    #[cfg(feature = "checked")]
+    return &self.ports[port];
+    #[cfg(not(feature = "checked"))]
     unsafe {
-      return self.ports.get_unchecked(port as usize);
+      return self.ports.get_unchecked(port);
     }
   }
 
   #[inline(always)]
   pub fn port_mut(&mut self, port: Port) -> &mut Ptr {
+    #[cfg(feature = "checked")]
+    return &mut self.ports[port];
+    #[cfg(not(feature = "checked"))]
     unsafe {
-      return self.ports.get_unchecked_mut(port as usize);
+      return self.ports.get_unchecked_mut(port);
+    }
+  }
+}
+
+impl std::fmt::Display for SymbolError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SymbolError::Invalid { name } => {
+        write!(f, "invalid name: '{}'", name)
+      }
+      SymbolError::Collision { name, id } => {
+        write!(f, "name collision: '{}' can't be bound to #{}", name, id)
+      }
+      SymbolError::Full => {
+        write!(f, "symbol table is full")
+      }
+    }
+  }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl Symbols {
+  // Gets the id of a name, giving it the next unused one if it has none yet.
+  pub fn intern(&mut self, name: &str) -> Result<Val, SymbolError> {
+    if let Some(&id) = self.ids.get(name) {
+      return Ok(id);
+    }
+    let id = self.names.len() as Val;
+    self.bind(name, id)?;
+    return Ok(id);
+  }
+
+  // Gives a name a chosen id, as when loading a book. Binding a pair again does nothing.
+  pub fn bind(&mut self, name: &str, id: Val) -> Result<(), SymbolError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+      return Err(SymbolError::Invalid { name: name.to_string() });
+    }
+    if id > VAL_MASK {
+      return Err(SymbolError::Full);
     }
+    let index = id as usize;
+    let other = self.names.get(index).and_then(|name| name.as_deref());
+    if other.is_some_and(|other| other != name) || self.ids.get(name).is_some_and(|&other| other != id) {
+      return Err(SymbolError::Collision { name: name.to_string(), id });
+    }
+    if self.names.len() <= index {
+      self.names.resize(index + 1, None);
+    }
+    self.names[index] = Some(name.to_string());
+    self.ids.insert(name.to_string(), id);
+    return Ok(());
+  }
+
+  // Gets the id of a name, if it has one.
+  pub fn id(&self, name: &str) -> Option<Val> {
+    return self.ids.get(name).copied();
+  }
+
+  // Gets the name of an id, if it has one.
+  pub fn name(&self, id: Val) -> Option<&str> {
+    return self.names.get(id as usize).and_then(|name| name.as_deref());
+  }
+
+  // Shows an id by its name, or as '#id' if it has none.
+  pub fn show(&self, id: Val) -> String {
+    return self.name(id).map_or_else(|| format!("#{}", id), str::to_string);
+  }
+
+  // Iterates over the ids that have a name, in order.
+  pub fn iter(&self) -> impl Iterator<Item = (Val, &str)> {
+    return self.names.iter().enumerate().filter_map(|(id, name)| Some((id as Val, name.as_deref()?)));
+  }
+}
+
+impl Default for Book {
+  fn default() -> Self {
+    Self::new()
   }
 }
 
 impl Book {
   pub fn new() -> Self {
-    Book { defs: HashMap::with_hasher(std::hash::BuildHasherDefault::default()) }
+    Book { defs: vec![], syms: Symbols::default() }
+  }
+
+  // Defines an id, replacing its earlier definition, if any.
+  pub fn def(&mut self, id: Val, def: Def) {
+    if self.defs.len() <= id as usize {
+      self.defs.resize(id as usize + 1, None);
+    }
+    self.defs[id as usize] = Some(def);
   }
 
-  pub fn def(&mut self, id: u32, net: Net) {
-    self.defs.insert(id, net);
+  // Removes the definition of an id, returning it.
+  pub fn undef(&mut self, id: Val) -> Option<Def> {
+    return self.defs.get_mut(id as usize).and_then(Option::take);
+  }
+
+  // Gets the definition of an id.
+  #[inline(always)]
+  pub fn get(&self, id: Val) -> Option<&Def> {
+    return self.defs.get(id as usize).and_then(Option::as_ref);
+  }
+
+  // Iterates the definitions, by order of id.
+  pub fn iter(&self) -> impl Iterator<Item = (Val, &Def)> {
+    return self.defs.iter().enumerate().filter_map(|(id, def)| Some((id as Val, def.as_ref()?)));
+  }
+
+  // Number of nodes of the largest definition.
+  pub fn largest(&self) -> usize {
+    return self.iter().map(|(_, def)| def.node.len()).max().unwrap_or(0);
+  }
+
+  // Number of nodes that an interaction between 'a' and 'b' may allocate: the expanded REFs, plus
+  // the clones made by a commutation. Fails if a REF that would be expanded can't be.
+  pub fn demand(&self, a: Ptr, b: Ptr) -> Result<usize, RuntimeError> {
+    let mut size = 4;
+    if a.is_ref() && !b.is_era() {
+      size += self.expansion(a)?;
+    }
+    if b.is_ref() && !a.is_era() {
+      size += self.expansion(b)?;
+    }
+    return Ok(size);
+  }
+
+  // Number of nodes allocated when expanding a REF, following REFs that expand to other REFs.
+  // Fails if one of them has no definition, or if they form a cycle, which would never end.
+  pub fn expansion(&self, ptr: Ptr) -> Result<usize, RuntimeError> {
+    let mut ptr = ptr;
+    let mut size = 0;
+    for _ in 0 ..= self.defs.len() {
+      if !ptr.is_ref() {
+        return Ok(size);
+      }
+      match self.get(ptr.val()) {
+        Some(got) => {
+          size += got.node.len();
+          ptr = got.root;
+        }
+        None => {
+          return Err(RuntimeError::MissingDef { id: ptr.val() });
+        }
+      }
+    }
+    return Err(RuntimeError::AliasCycle { id: ptr.val() });
+  }
+}
+
+impl From<Net> for Def {
+  // Keeps the nodes, redexes and root of a net, dropping its allocator and counters. Its nodes
+  // are taken as they are, so a net with free nodes gives a definition that allocates them too.
+  fn from(net: Net) -> Self {
+    return Def { root: net.root, acts: net.acts, node: net.node };
+  }
+}
+
+impl Def {
+  // Checks that a definition only points to its own nodes, with tags that can be where they are.
+  pub(crate) fn check_def(&self) -> Result<(), RuntimeError> {
+    let ports = self.node.iter().flat_map(|node| node.ports);
+    let acts = self.acts.iter().flat_map(|&(a, b)| [a, b]);
+    for ptr in ports.chain(acts).chain([self.root]) {
+      if ptr.tag() == NIL || ptr.tag() > MAT || ptr.is_red() {
+        return Err(RuntimeError::BadTag { ptr });
+      }
+      if ptr.has_loc() && ptr.loc() as usize >= self.node.len() {
+        return Err(RuntimeError::BadPointer { ptr });
+      }
+    }
+    for &(a, b) in &self.acts {
+      if !a.is_pri() || !b.is_pri() {
+        return Err(RuntimeError::BadTag { ptr: if a.is_pri() { b } else { a } });
+      }
+    }
+    return Ok(());
+  }
+}
+
+impl Stats {
+  // Adds the counts of another reduction, such as that of a parallel worker.
+  pub fn merge(&mut self, other: &Stats) {
+    self.anni += other.anni;
+    self.comm += other.comm;
+    self.eras += other.eras;
+    self.copy += other.copy;
+    self.oper += other.oper;
+    self.coll += other.coll;
+    self.link += other.link;
+    self.void += other.void;
+    self.dref += other.dref;
+    self.peak  = self.peak.max(other.peak);
+    self.rnds += other.rnds;
+  }
+
+  // Counts a rewrite by its rule.
+  #[inline(always)]
+  pub fn count(&mut self, rule: Rule) {
+    match rule {
+      Rule::Anni => self.anni += 1,
+      Rule::Comm => self.comm += 1,
+      Rule::Eras => self.eras += 1,
+      Rule::Copy => self.copy += 1,
+      Rule::Oper => self.oper += 1,
+      Rule::Coll => self.coll += 1,
+      Rule::Link => self.link += 1,
+      Rule::Void => self.void += 1,
+    }
+  }
+}
+
+impl std::fmt::Display for Stats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "anni: {}", self.anni)?;
+    writeln!(f, "comm: {}", self.comm)?;
+    writeln!(f, "eras: {}", self.eras)?;
+    writeln!(f, "copy: {}", self.copy)?;
+    writeln!(f, "oper: {}", self.oper)?;
+    writeln!(f, "coll: {}", self.coll)?;
+    writeln!(f, "link: {}", self.link)?;
+    writeln!(f, "void: {}", self.void)?;
+    writeln!(f, "dref: {}", self.dref)?;
+    writeln!(f, "peak: {}", self.peak)?;
+    write!(f, "rnds: {}", self.rnds)
+  }
+}
+
+impl Default for Profile {
+  fn default() -> Self {
+    Profile::new()
+  }
+}
+
+impl Profile {
+  pub fn new() -> Self {
+    Profile { defs: BTreeMap::new(), owns: vec![], curr: NO_DEF }
+  }
+
+  // Gets the owner of a node.
+  #[inline(always)]
+  fn owner(&self, loc: Val) -> Val {
+    return self.owns.get(loc as usize).copied().unwrap_or(NO_DEF);
+  }
+
+  // Sets the owner of a node.
+  #[inline(always)]
+  fn own(&mut self, loc: Val, def: Val) {
+    if self.owns.len() <= loc as usize {
+      self.owns.resize(loc as usize + 1, NO_DEF);
+    }
+    self.owns[loc as usize] = def;
+  }
+
+  // Blames a rewrite between 'a' and 'b' on the owner of their nodes, or on their REF.
+  fn blame(&mut self, a: Ptr, b: Ptr) {
+    self.curr = if a.has_loc() {
+      self.owner(a.loc())
+    } else if b.has_loc() {
+      self.owner(b.loc())
+    } else if a.is_ref() {
+      a.val()
+    } else if b.is_ref() {
+      b.val()
+    } else {
+      NO_DEF
+    };
+    if self.curr != NO_DEF {
+      self.defs.entry(self.curr).or_default().rwts += 1;
+    }
+  }
+
+  // Records an expansion of a definition, which loaded nodes into 'locs', and injected 'acts'
+  // redexes.
+  fn load(&mut self, def: Val, locs: &[Val], acts: usize) {
+    for &loc in locs {
+      self.own(loc, def);
+    }
+    let cost = self.defs.entry(def).or_default();
+    cost.dref += 1;
+    cost.node += locs.len();
+    cost.acts += acts;
+  }
+}
+
+impl Profile {
+  // Shows a line per definition, the costliest first, naming them by the given symbols.
+  pub fn show(&self, syms: &Symbols) -> String {
+    let mut defs = self.defs.iter().collect::<Vec<_>>();
+    defs.sort_by_key(|(_, cost)| std::cmp::Reverse(cost.rwts));
+    let lines = defs.into_iter().map(|(def, cost)| {
+      format!("@{}: dref {} node {} acts {} rwts {}", syms.show(*def), cost.dref, cost.node, cost.acts, cost.rwts)
+    });
+    return lines.collect::<Vec<_>>().join("\n");
+  }
+}
+
+impl std::fmt::Display for Profile {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.show(&Symbols::default()))
+  }
+}
+
+impl Budget {
+  // A budget of 'max_step' rewrites, with no other limit.
+  pub fn steps(max_step: usize) -> Self {
+    Budget { rwts: Some(max_step), ..Budget::default() }
+  }
+
+  // Starts spending this budget.
+  pub fn start(&self) -> Meter {
+    Meter {
+      max_step: self.rwts.unwrap_or(usize::MAX),
+      deadline: self.time.map(|time| Instant::now() + time),
+    }
+  }
+}
+
+impl Meter {
+  // Checks if the deadline has passed.
+  pub fn late(&self) -> bool {
+    return self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+  }
+
+  // Checks if a net has spent this budget, returning how its reduction ends.
+  pub fn spent(&self, net: &Net) -> Option<Outcome> {
+    if net.rwts >= self.max_step {
+      return Some(Outcome::OutOfFuel);
+    }
+    if self.late() {
+      return Some(Outcome::Timeout);
+    }
+    return None;
+  }
+}
+
+impl Worker {
+  // Creates a worker with enough scratch space for the largest definition of a book.
+  pub fn new(book: &Book) -> Self {
+    Worker::with_observer(book, ())
+  }
+}
+
+impl<O: ReductionObserver> Worker<O> {
+  // Creates a worker that tells an observer about the reductions it performs.
+  pub fn with_observer(book: &Book, obs: O) -> Self {
+    Worker { locs: vec![0; book.largest()], owns: vec![], obs }
   }
 }
 
 impl Net {
-  // Creates an empty net with given size.
+  // Creates an empty net that can grow up to given size, or up to what pointers can address.
   pub fn new(size: usize) -> Self {
+    let size = size.min(VAL_MASK as usize + 1);
     Net {
       root: Ptr::new(NIL, 0),
       acts: vec![],
-      node: vec![Node::nil(); size],
-      next: 0,
+      node: vec![Node::nil(); size.min(PAGE)],
+      limit: size,
+      page: Pages {
+        next: 0,
+        stop: 0,
+        curr: None,
+        full: false,
+        more: 0,
+        scan: 0,
+        live: vec![],
+        free: vec![],
+      },
       used: 0,
       rwts: 0,
-      locs: vec![0; 1 << 16], // FIXME: should be field of Worker, not Net
+      stats: Stats::default(),
+      prof: None,
     }
   }
 
+  // Starts profiling the definitions expanded by sequential reductions, clearing earlier costs.
+  pub fn profile(&mut self) {
+    self.prof = Some(Box::default());
+  }
+
   //pub fn tmp_redir(&mut self) {
     //self.root = self.root.tmp_redir(self);
     //for i in 0 .. self.node.len() {
@@ -240,38 +1013,222 @@ impl Net {
   //}
 
   // Creates a net and boots from a REF.
-  pub fn boot(&mut self, root_id: u32) {
+  pub fn boot(&mut self, root_id: Val) {
     self.root = Ptr::new(REF, root_id);
   }
 
-  // Allocates a consecutive chunk of 'size' nodes. Returns the index.
+  // Allocates a single node. Returns the index.
   #[inline(always)]
   pub fn alloc(&mut self) -> Val {
-    let starting_index = self.next;
     loop {
-      if self.next >= self.node.len() {
-        self.next = 0;
+      if self.page.next >= self.page.stop {
+        self.enter_page(false);
       }
-      if self.get(self.next as Val, P1).tag() == NIL {
-        self.next += 1;
-        self.used += 1;
-        return (self.next - 1) as Val;
+      let index = self.page.next;
+      self.page.next += 1;
+      // On partially empty pages, skips allocated nodes.
+      if self.page.full && *self.at(index as Val) != Node::nil() {
+        continue;
       }
-      self.next += 1;
-      debug_assert!(self.next != starting_index);
+      self.reserve(index);
+      self.page.live[index / PAGE] += 1;
+      self.used += 1;
+      self.stats.peak = self.stats.peak.max(self.used);
+      if let Some(prof) = &mut self.prof {
+        prof.own(index as Val, prof.curr);
+      }
+      return index as Val;
     }
   }
 
+  // Marks an allocated node as taken until the caller writes to it, so that searching partially
+  // empty pages doesn't give it away again.
+  #[inline(always)]
+  fn reserve(&mut self, index: usize) {
+    unsafe {
+      *self.node.get_unchecked_mut(index).port_mut(P1) = Ptr::new(NIL, 1);
+    }
+  }
+
+  // Allocates a consecutive chunk of 'size' nodes. Returns the index, or None if there is no
+  // consecutive space left.
+  pub fn alloc_chunk(&mut self, size: usize) -> Option<Val> {
+    // Empty chunks need no page, so any index will do.
+    if size == 0 {
+      return Some(0);
+    }
+    // Large chunks span many pages, which are taken from the never used ones.
+    if size > PAGE {
+      let index = self.page.more * PAGE;
+      if index + size > self.node.len() {
+        return None;
+      }
+      self.page.more += size.div_ceil(PAGE);
+      for i in (index .. index + size).step_by(PAGE) {
+        self.page.live[i / PAGE] += PAGE.min(index + size - i) as u32;
+      }
+      for i in index .. index + size {
+        self.reserve(i);
+      }
+      self.used += size;
+      self.stats.peak = self.stats.peak.max(self.used);
+      return Some(index as Val);
+    }
+    // Small chunks are bumped on an empty page.
+    let fits = !self.page.full && self.page.next + size <= self.page.stop;
+    if !fits && (!self.enter_page(true) || self.page.next + size > self.page.stop) {
+      return None;
+    }
+    let index = self.page.next;
+    self.page.next += size;
+    self.page.live[index / PAGE] += size as u32;
+    for i in index .. index + size {
+      self.reserve(i);
+    }
+    self.used += size;
+    self.stats.peak = self.stats.peak.max(self.used);
+    return Some(index as Val);
+  }
+
   // Frees the memory used by a single node.
   #[inline(always)]
   pub fn free(&mut self, val: Val) {
+    let page = val as usize / PAGE;
     self.used -= 1;
     self.node[val as usize] = Node::nil();
+    self.page.live[page] -= 1;
+    if self.page.live[page] == 0 && self.page.curr != Some(page) {
+      self.page.free.push(page);
+    }
+  }
+
+  // Moves the allocator to another page, preferring empty ones, and growing the heap when it is
+  // at least half full. If 'empty' is set, fails instead of moving to a partially empty page.
+  fn enter_page(&mut self, empty: bool) -> bool {
+    if self.page.free.is_empty() && self.page.more * PAGE >= self.node.len() && self.used * 2 >= self.node.len() {
+      self.grow(0);
+    }
+    let pages = self.node.len().div_ceil(PAGE);
+    if self.page.live.len() < pages {
+      self.page.live.resize(pages, 0);
+    }
+    let (page, full) = if let Some(page) = self.page.free.pop() {
+      (page, false)
+    } else if self.page.more < pages {
+      self.page.more += 1;
+      (self.page.more - 1, false)
+    } else if empty {
+      return false;
+    } else {
+      let found = (0 .. pages).map(|i| (self.page.scan + i) % pages).find(|&page| {
+        (self.page.live[page] as usize) < PAGE.min(self.node.len() - page * PAGE)
+      });
+      match found {
+        Some(page) => { self.page.scan = (page + 1) % pages; (page, true) }
+        // Every allocation is checked by 'room_for' beforehand, or has no limit, as when parsing.
+        // So either some page has room, or the heap is below its limit and was just grown.
+        None       => { unreachable!("no room on a heap of {} nodes with {} used", self.node.len(), self.used); }
+      }
+    };
+    // Leaves the current page, recycling it if it is empty.
+    if let Some(curr) = self.page.curr {
+      if self.page.live[curr] == 0 && curr != page {
+        self.page.free.push(curr);
+      }
+    }
+    self.page.curr = Some(page);
+    self.page.full = full;
+    self.page.next = page * PAGE;
+    self.page.stop = (page * PAGE + PAGE).min(self.node.len());
+    return true;
+  }
+
+  // Grows the heap to at least 'size' nodes, at least doubling it, but never past 'limit'.
+  // Returns false if it couldn't grow.
+  pub(crate) fn grow(&mut self, size: usize) -> bool {
+    let size = size.max(self.node.len() * 2).max(PAGE).min(self.limit);
+    if size <= self.node.len() {
+      return false;
+    }
+    self.node.resize(size, Node::nil());
+    return true;
+  }
+
+  // Checks that the heap can give 'size' more nodes.
+  #[inline(always)]
+  pub(crate) fn room_for(&self, size: usize) -> Result<(), RuntimeError> {
+    if self.used + size > self.limit {
+      return Err(RuntimeError::OutOfMemory { used: self.used, limit: self.limit });
+    }
+    return Ok(());
+  }
+
+  // Rebuilds the allocator state by scanning the heap. Must be called after nodes were allocated
+  // or freed by other means, such as the parallel reducer, or after the heap was shrunk.
+  pub(crate) fn reclaim(&mut self) {
+    let pages = self.node.len().div_ceil(PAGE);
+    let mut live = vec![0; pages];
+    for (index, node) in self.node.iter_mut().enumerate() {
+      // The parallel reducer frees nodes by clearing their ports, and leaves their label behind.
+      if node.ports == Node::nil().ports {
+        node.lab = 0;
+      } else {
+        live[index / PAGE] += 1;
+      }
+    }
+    self.page = Pages {
+      next: 0,
+      stop: 0,
+      curr: None,
+      full: false,
+      more: pages,
+      scan: 0,
+      free: (0 .. pages).rev().filter(|&page| live[page] == 0).collect(),
+      live,
+    };
+  }
+
+  // Moves the live nodes to a dense prefix of the heap, keeping their order, and updates every
+  // pointer to them, including 'root' and 'acts'. If 'shrink' is set, the heap is then cut to the
+  // pages that hold them. Meant to be called between reductions, on long-running nets.
+  pub fn compact(&mut self, shrink: bool) {
+    let mut locs = vec![0; self.node.len()];
+    let mut used : usize = 0;
+    for (index, node) in self.node.iter().enumerate() {
+      if *node != Node::nil() {
+        locs[index] = used as Val;
+        used += 1;
+      }
+    }
+    // Nodes only move backwards, so each one is read before its place is taken.
+    for index in 0 .. self.node.len() {
+      let node = self.node[index];
+      if node != Node::nil() {
+        self.node[index] = Node::nil();
+        self.node[locs[index] as usize] = Node::new(node.port(P1).adjust(&locs), node.port(P2).adjust(&locs), node.lab);
+        if let Some(prof) = &mut self.prof {
+          prof.own(locs[index], prof.owner(index as Val));
+        }
+      }
+    }
+    self.root = self.root.adjust(&locs);
+    for (a, b) in &mut self.acts {
+      *a = a.adjust(&locs);
+      *b = b.adjust(&locs);
+    }
+    if shrink {
+      self.node.truncate(used.next_multiple_of(PAGE).max(PAGE));
+      self.node.shrink_to_fit();
+    }
+    self.reclaim();
   }
 
   // Gets node at given index.
   #[inline(always)]
-  pub fn at(&self, index: u32) -> &Node {
+  pub fn at(&self, index: Val) -> &Node {
+    #[cfg(feature = "checked")]
+    return &self.node[index as usize];
+    #[cfg(not(feature = "checked"))]
     unsafe {
       return self.node.get_unchecked(index as usize);
     }
@@ -279,7 +1236,10 @@ impl Net {
 
   // Gets node at given index, mutable.
   #[inline(always)]
-  pub fn at_mut(&mut self, index: u32) -> &mut Node {
+  pub fn at_mut(&mut self, index: Val) -> &mut Node {
+    #[cfg(feature = "checked")]
+    return &mut self.node[index as usize];
+    #[cfg(not(feature = "checked"))]
     unsafe {
       return self.node.get_unchecked_mut(index as usize);
     }
@@ -302,6 +1262,13 @@ impl Net {
   // - Otherwise, this is an redexes, so we add it to 'acts'.
   #[inline(always)]
   pub fn link(&mut self, a: Ptr, b: Ptr) {
+    self.link_with(&mut (), a, b);
+  }
+
+  // Same as 'link', telling an observer about the new wire.
+  #[inline(always)]
+  pub fn link_with<O: ReductionObserver>(&mut self, obs: &mut O, a: Ptr, b: Ptr) {
+    obs.link(a, b);
     // Substitutes A
     if a.is_var() {
       *a.target(self).unwrap() = b;
@@ -316,149 +1283,531 @@ impl Net {
     }
   }
 
-  // Performs an interaction over a redex.
+  // Performs an interaction over a redex. Fails if a REF has no definition, or, with the 'checked'
+  // feature, if the redex is malformed.
   #[inline(always)]
-  pub fn interact(&mut self, book: &Book, a: &mut Ptr, b: &mut Ptr) {
+  pub fn interact<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, a: &mut Ptr, b: &mut Ptr) -> Result<(), RuntimeError> {
+    #[cfg(feature = "checked")]
+    self.check_redex(*a, *b)?;
+    let (redex, used) = ((*a, *b), self.used);
     self.rwts += 1;
+    if let Some(prof) = &mut self.prof {
+      prof.blame(*a, *b);
+    }
     // Dereference
     if a.tag() == REF && b.tag() != ERA {
-      *a = self.deref(book, *a, Ptr::new(NIL,0), &mut 0);
+      *a = self.deref(book, work, *a, Ptr::new(NIL,0))?;
     }
     if a.tag() != ERA && b.tag() == REF {
-      *b = self.deref(book, *b, Ptr::new(NIL,0), &mut 0);
+      *b = self.deref(book, work, *b, Ptr::new(NIL,0))?;
     }
     // VAR
-    if a.is_var() || b.is_var() {
-      self.link(*a, *b);
+    let (rule, freed) = if a.is_var() || b.is_var() {
+      self.link_with(&mut work.obs, *a, *b);
+      (Rule::Link, 0)
     // CON-CON
-    } else if a.is_ctr() && b.is_ctr() && a.tag() == b.tag() {
-      let a1 = self.get(a.val(), P1);
-      let b1 = self.get(b.val(), P1);
-      self.link(a1, b1);
-      let a2 = self.get(a.val(), P2);
-      let b2 = self.get(b.val(), P2);
-      self.link(a2, b2);
-      self.free(a.val());
-      self.free(b.val());
-    // CON-DUP
-    } else if a.is_ctr() && b.is_ctr() && a.tag() != b.tag() {
-      let x1 = self.alloc();
-      let x2 = self.alloc();
-      let y1 = self.alloc();
-      let y2 = self.alloc();
-      self.set(x1, P1, Ptr::new(VR1, y1));
-      self.set(x1, P2, Ptr::new(VR1, y2));
-      self.set(x2, P1, Ptr::new(VR2, y1));
-      self.set(x2, P2, Ptr::new(VR2, y2));
-      self.set(y1, P1, Ptr::new(VR1, x1));
-      self.set(y1, P2, Ptr::new(VR1, x2));
-      self.set(y2, P1, Ptr::new(VR2, x1));
-      self.set(y2, P2, Ptr::new(VR2, x2));
-      self.link(self.get(a.val(), P1), Ptr::new(b.tag(), x1));
-      self.link(self.get(a.val(), P2), Ptr::new(b.tag(), x2));
-      self.link(self.get(b.val(), P1), Ptr::new(a.tag(), y1));
-      self.link(self.get(b.val(), P2), Ptr::new(a.tag(), y2));
-      self.free(a.val());
-      self.free(b.val());
+    } else if a.is_ctr() && b.is_ctr() && self.at(a.loc()).lab == self.at(b.loc()).lab {
+      let a1 = self.get(a.loc(), P1);
+      let b1 = self.get(b.loc(), P1);
+      self.link_with(&mut work.obs, a1, b1);
+      let a2 = self.get(a.loc(), P2);
+      let b2 = self.get(b.loc(), P2);
+      self.link_with(&mut work.obs, a2, b2);
+      self.free(a.loc());
+      self.free(b.loc());
+      (Rule::Anni, 2)
+    // CON-DUP, and any pair with an operation or match node
+    } else if a.is_nod() && b.is_nod() {
+      self.commute(&mut work.obs, *a, *b);
+      (Rule::Comm, 2)
+    // OP2-NUM
+    } else if a.tag() == OP2 && b.is_num() {
+      self.op2(&mut work.obs, *a, *b);
+      (Rule::Oper, 0)
+    // NUM-OP2
+    } else if a.is_num() && b.tag() == OP2 {
+      self.op2(&mut work.obs, *b, *a);
+      (Rule::Oper, 0)
+    // OP1-NUM
+    } else if a.tag() == OP1 && b.is_num() {
+      self.op1(&mut work.obs, *a, *b);
+      (Rule::Oper, 1)
+    // NUM-OP1
+    } else if a.is_num() && b.tag() == OP1 {
+      self.op1(&mut work.obs, *b, *a);
+      (Rule::Oper, 1)
+    // MAT-NUM
+    } else if a.is_mat() && b.is_num() {
+      self.mat(&mut work.obs, *a, *b);
+      (Rule::Oper, 1)
+    // NUM-MAT
+    } else if a.is_num() && b.is_mat() {
+      self.mat(&mut work.obs, *b, *a);
+      (Rule::Oper, 1)
     // CTR-NUM
-    } else if a.is_ctr() && b.is_num() { // TODO: test
-      self.link(self.get(a.val(), P1), Ptr::new(NUM, b.val()));
-      self.link(self.get(a.val(), P2), Ptr::new(NUM, b.val()));
-      self.free(a.val());
+    } else if a.is_ctr() && b.is_num() {
+      self.link_with(&mut work.obs, self.get(a.loc(), P1), Ptr::new(NUM, b.val()));
+      self.link_with(&mut work.obs, self.get(a.loc(), P2), Ptr::new(NUM, b.val()));
+      self.free(a.loc());
+      (Rule::Copy, 1)
     // NUM-CTR
-    } else if a.is_num() && b.is_ctr() { // TODO: test
-      self.interact(book, b, a);
-    // CON-ERA
-    } else if a.is_ctr() && b.is_era() {
-      self.link(self.get(a.val(), P1), Ptr::new(ERA, 0));
-      self.link(self.get(a.val(), P2), Ptr::new(ERA, 0));
-      self.free(a.val());
-    // ERA-CON
-    } else if a.is_era() && b.is_ctr() {
-      self.link(self.get(b.val(), P1), Ptr::new(ERA, 0));
-      self.link(self.get(b.val(), P2), Ptr::new(ERA, 0));
-      self.free(b.val());
+    } else if a.is_num() && b.is_ctr() {
+      self.link_with(&mut work.obs, self.get(b.loc(), P1), Ptr::new(NUM, a.val()));
+      self.link_with(&mut work.obs, self.get(b.loc(), P2), Ptr::new(NUM, a.val()));
+      self.free(b.loc());
+      (Rule::Copy, 1)
+    // NOD-ERA
+    } else if a.is_nod() && b.is_era() {
+      self.link_with(&mut work.obs, self.get(a.loc(), P1), Ptr::new(ERA, 0));
+      self.link_with(&mut work.obs, self.get(a.loc(), P2), Ptr::new(ERA, 0));
+      self.free(a.loc());
+      (Rule::Eras, 1)
+    // ERA-NOD
+    } else if a.is_era() && b.is_nod() {
+      self.link_with(&mut work.obs, self.get(b.loc(), P1), Ptr::new(ERA, 0));
+      self.link_with(&mut work.obs, self.get(b.loc(), P2), Ptr::new(ERA, 0));
+      self.free(b.loc());
+      (Rule::Eras, 1)
+    // REF-ERA
+    } else if a.is_ref() || b.is_ref() {
+      (Rule::Coll, 0)
+    // Otherwise, both sides are ERAs or NUMs, which just vanish.
+    } else {
+      (Rule::Void, 0)
+    };
+    self.stats.count(rule);
+    work.obs.rewrite(&Rewrite { rule, a: redex.0, b: redex.1, alloc: self.used + freed - used, freed });
+    return Ok(());
+  }
+
+  // Checks that a redex is made of principal ports, and that the nodes it consumes are on the heap,
+  // with ports that can be read and linked.
+  #[cfg(feature = "checked")]
+  fn check_redex(&self, a: Ptr, b: Ptr) -> Result<(), RuntimeError> {
+    for ptr in [a, b] {
+      if !ptr.is_pri() {
+        return Err(RuntimeError::BadTag { ptr });
+      }
+      if ptr.is_nod() {
+        if ptr.loc() as usize >= self.node.len() || *self.at(ptr.loc()) == Node::nil() {
+          return Err(RuntimeError::BadPointer { ptr });
+        }
+        for port in [P1, P2] {
+          let port = self.get(ptr.loc(), port);
+          if port.tag() == NIL || port.tag() > MAT || port.is_red() {
+            return Err(RuntimeError::BadTag { ptr: port });
+          }
+          if port.is_var() && port.tag() != VRR && (port.loc() as usize >= self.node.len() || *self.at(port.loc()) == Node::nil()) {
+            return Err(RuntimeError::BadPointer { ptr: port });
+          }
+        }
+      }
+    }
+    return Ok(());
+  }
+
+  // Commutes two nodes, sending a clone of each one to every aux port of the other. An OP1 node has
+  // a single aux port, P2, as its P1 holds the left operand, which its clones keep.
+  fn commute<O: ReductionObserver>(&mut self, obs: &mut O, a: Ptr, b: Ptr) {
+    let a_aux: &[Port] = if a.tag() == OP1 { &[P2] } else { &[P1, P2] };
+    let b_aux: &[Port] = if b.tag() == OP1 { &[P2] } else { &[P1, P2] };
+    let mut x = [0; 2];
+    let mut y = [0; 2];
+    let (a_lab, b_lab) = (self.at(a.loc()).lab, self.at(b.loc()).lab);
+    for x in &mut x[0 .. a_aux.len()] {
+      *x = self.alloc();
+      self.at_mut(*x).lab = b_lab;
     }
+    for y in &mut y[0 .. b_aux.len()] {
+      *y = self.alloc();
+      self.at_mut(*y).lab = a_lab;
+    }
+    for (i, &a_port) in a_aux.iter().enumerate() {
+      for (j, &b_port) in b_aux.iter().enumerate() {
+        self.set(x[i], b_port, Ptr::new(if a_port == P1 { VR1 } else { VR2 }, y[j]));
+        self.set(y[j], a_port, Ptr::new(if b_port == P1 { VR1 } else { VR2 }, x[i]));
+      }
+    }
+    if a.tag() == OP1 {
+      for &y in &y[0 .. b_aux.len()] {
+        self.set(y, P1, self.get(a.loc(), P1));
+      }
+    }
+    if b.tag() == OP1 {
+      for &x in &x[0 .. a_aux.len()] {
+        self.set(x, P1, self.get(b.loc(), P1));
+      }
+    }
+    for (i, &a_port) in a_aux.iter().enumerate() {
+      self.link_with(obs, self.get(a.loc(), a_port), Ptr::new(b.tag(), x[i]));
+    }
+    for (j, &b_port) in b_aux.iter().enumerate() {
+      self.link_with(obs, self.get(b.loc(), b_port), Ptr::new(a.tag(), y[j]));
+    }
+    self.free(a.loc());
+    self.free(b.loc());
+  }
+
+  // Gives the left operand 'b' to an OP2 node 'a', which holds it and waits for the right operand
+  // as an OP1 node. This happens even if the right operand is already a number, so that every
+  // operation takes the same rewrites, whichever operand comes first.
+  fn op2<O: ReductionObserver>(&mut self, obs: &mut O, a: Ptr, b: Ptr) {
+    let rgt = self.get(a.loc(), P1);
+    self.set(a.loc(), P1, b);
+    self.link_with(obs, rgt, Ptr::new(OP1, a.loc()));
   }
 
-  // Expands a REF into its definition (a closed net).
+  // Gives the right operand 'b' to an OP1 node 'a', sending the result to its output.
+  fn op1<O: ReductionObserver>(&mut self, obs: &mut O, a: Ptr, b: Ptr) {
+    let lft = self.get(a.loc(), P1);
+    self.link_with(obs, self.get(a.loc(), P2), lft.operate(b));
+    self.free(a.loc());
+  }
+
+  // Gives the number 'b' to a MAT node 'a', whose P1 holds the branches '(0 zero succ)'. If it is
+  // zero, the branches become '(0 r *)'; otherwise, they become '(0 * (0 pred r))', where 'r' is
+  // the output of the node.
+  fn mat<O: ReductionObserver>(&mut self, obs: &mut O, a: Ptr, b: Ptr) {
+    let out = self.get(a.loc(), P2);
+    let arms = self.alloc();
+    if b.num() == 0 {
+      self.set(arms, P2, Ptr::new(ERA, 0));
+      self.link_with(obs, out, Ptr::new(VR1, arms));
+    } else {
+      let succ = self.alloc();
+      self.set(succ, P1, Ptr::new_num(USE, b.num() - 1));
+      self.link_with(obs, out, Ptr::new(VR2, succ));
+      self.set(arms, P1, Ptr::new(ERA, 0));
+      self.set(arms, P2, Ptr::new(CTR, succ));
+    }
+    self.link_with(obs, self.get(a.loc(), P1), Ptr::new(CTR, arms));
+    self.free(a.loc());
+  }
+
+  // Expands a REF into its definition (a closed net). Fails if there is no such definition, or, with
+  // the 'checked' feature, if it is malformed.
   #[inline(always)]
-  pub fn deref(&mut self, book: &Book, ptr: Ptr, parent: Ptr, loc: &mut usize) -> Ptr {
+  pub fn deref<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, ptr: Ptr, parent: Ptr) -> Result<Ptr, RuntimeError> {
     let mut ptr = ptr;
+    let mut hops = 0;
     // White ptr is still a REF...
     while ptr.is_ref() {
+      // Stops once some definition must have been loaded twice...
+      hops += 1;
+      if hops > book.defs.len() {
+        return Err(RuntimeError::AliasCycle { id: ptr.val() });
+      }
       // Loads the referenced definition...
-      if let Some(got) = book.defs.get(&ptr.val()) {
-        let ini = *loc;
-        *loc += got.node.len();
-        // Allocates enough space...
+      if let Some(got) = book.get(ptr.val()) {
+        #[cfg(feature = "checked")]
+        got.check_def()?;
+        self.stats.dref += 1;
+        // Makes room for its locations, if it was defined after the worker was created...
+        if work.locs.len() < got.node.len() {
+          work.locs.resize(got.node.len(), 0);
+        }
+        // Allocates enough space, consecutively if possible...
+        let chunk = self.alloc_chunk(got.node.len());
         for i in 0 .. got.node.len() {
+          let index = match chunk {
+            Some(start) => start + i as Val,
+            None        => self.alloc(),
+          };
           unsafe {
-            *self.locs.get_unchecked_mut(ini + i) = self.alloc();
+            *work.locs.get_unchecked_mut(i) = index;
           }
         }
         // Loads nodes, adjusting locations...
         for i in 0 .. got.node.len() {
           unsafe {
-            let got = got.node.get_unchecked(i).clone();
-            let p1  = got.port(P1).adjust(&self.locs[ini..]);
-            let p2  = got.port(P2).adjust(&self.locs[ini..]);
-            *self.at_mut(*self.locs.get_unchecked(ini + i)) = Node::new(p1, p2);
+            let got = *got.node.get_unchecked(i);
+            let p1  = got.port(P1).adjust(&work.locs);
+            let p2  = got.port(P2).adjust(&work.locs);
+            *self.at_mut(*work.locs.get_unchecked(i)) = Node::new(p1, p2, got.lab);
           }
         }
         // Loads redexes, adjusting locations...
         for got in &got.acts {
-          let p1 = got.0.adjust(&self.locs[ini..]);
-          let p2 = got.1.adjust(&self.locs[ini..]);
+          let p1 = got.0.adjust(&work.locs);
+          let p2 = got.1.adjust(&work.locs);
           self.acts.push((p1, p2));
         }
+        if let Some(prof) = &mut self.prof {
+          prof.load(ptr.val(), &work.locs[.. got.node.len()], got.acts.len());
+        }
+        work.obs.deref(ptr.val(), got.node.len());
         // Overwrites 'ptr' with the loaded root pointer, adjusting locations...
-        ptr = got.root.adjust(&self.locs[ini..]);
+        ptr = got.root.adjust(&work.locs);
         // Links root
         if ptr.is_var() {
           if let Some(trg) = ptr.target(self) {
             *trg = parent;
           }
         }
+      } else {
+        return Err(RuntimeError::MissingDef { id: ptr.val() });
       }
     }
-    return ptr;
+    return Ok(ptr);
   }
 
-  // Performs a global parallel rewrite.
-  pub fn reduce(&mut self, book: &Book) -> usize {
-    let rwts = self.acts.len();
-    let acts = std::mem::replace(&mut self.acts, vec![]);
+  // Performs a global parallel rewrite. Stops early if the heap would outgrow its limit, keeping
+  // the redexes that were not reduced.
+  pub fn reduce(&mut self, book: &Book) -> Result<usize, RuntimeError> {
+    return self.reduce_in(book, &mut Worker::new(book));
+  }
+
+  // Same as 'reduce', using the scratch space and observer of a given worker.
+  pub fn reduce_in<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>) -> Result<usize, RuntimeError> {
+    let done = self.reduce_upto(book, work, &Budget::default().start());
+    self.debug_validate();
+    return done;
+  }
+
+  // Same as 'reduce_in', stopping once the net has performed the rewrites of 'meter', or once its
+  // deadline has passed, which is checked every TICK redexes. The redexes left are kept at the
+  // start of 'acts', before those created by this round, so the next round reduces them first.
+  pub(crate) fn reduce_upto<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, meter: &Meter) -> Result<usize, RuntimeError> {
+    let acts = std::mem::take(&mut self.acts);
+    let rwts = acts.len().min(meter.max_step.saturating_sub(self.rwts));
+    self.stats.rnds += 1;
+    work.obs.round(self.stats.rnds, rwts);
     // This loop can be parallelized!
-    for (mut a, mut b) in acts {
-      self.interact(book, &mut a, &mut b);
+    for i in 0 .. rwts {
+      if i > 0 && i.is_multiple_of(TICK) && meter.late() {
+        self.keep(acts, i);
+        return Ok(i);
+      }
+      let (mut a, mut b) = acts[i];
+      if let Err(err) = book.demand(a, b).and_then(|size| self.room_for(size)) {
+        self.keep(acts, i);
+        return Err(err);
+      }
+      if let Err(err) = self.interact(book, work, &mut a, &mut b) {
+        self.keep(acts, i + 1);
+        return Err(err);
+      }
+    }
+    self.keep(acts, rwts);
+    return Ok(rwts);
+  }
+
+  // Puts back the redexes of a round that were not reduced, from index 'done' on.
+  fn keep(&mut self, mut acts: Vec<(Ptr, Ptr)>, done: usize) {
+    if done < acts.len() {
+      acts.drain(.. done);
+      acts.append(&mut self.acts);
+      self.acts = acts;
+    }
+  }
+
+  // Performs up to 'max_rwts' more rewrites, continuing the reduction that earlier calls started,
+  // in the same order that 'normal' would: rounds stopped halfway are finished first, and the head
+  // is expanded when the redexes run out. Returns whether the net is in normal form. Running out of
+  // memory keeps the pending redexes, so a later slice can go on once there is room.
+  pub fn step_for(&mut self, book: &Book, max_rwts: usize) -> Result<Progress, RuntimeError> {
+    let work = &mut Worker::new(book);
+    let meter = Budget::steps(self.rwts.saturating_add(max_rwts)).start();
+    loop {
+      if self.acts.is_empty() {
+        self.expand_with(book, work, Ptr::new(VRR, 0))?;
+        if self.acts.is_empty() {
+          return Ok(Progress::Done);
+        }
+      }
+      if self.rwts >= meter.max_step {
+        return Ok(Progress::Pending);
+      }
+      let done = self.reduce_upto(book, work, &meter);
+      self.debug_validate();
+      done?;
     }
-    return rwts;
   }
 
-  // Reduces all redexes until there is none.
-  pub fn normal(&mut self, book: &Book, max_step: Option<usize>) {
-    self.expand(book, Ptr::new(VRR, 0));
-    let max_step = max_step.unwrap_or(usize::MAX);
-    while self.acts.len() > 0 && self.rwts < max_step {
-      while self.acts.len() > 0 && self.rwts < max_step {
-        // println!(">> reduce {}", self.acts.len());
-        self.reduce(book);
-        //self.expand(book, Ptr::new(VRR, 0));
+  // Reduces all redexes until there is none, or until the budget is spent. Returns how it ended,
+  // with the statistics of the net.
+  pub fn normal(&mut self, book: &Book, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    return self.normal_in(book, &mut Worker::new(book), budget);
+  }
+
+  // Same as 'normal', using the scratch space and observer of a given worker.
+  pub fn normal_in<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    return self.spend(budget, |net, meter| {
+      net.expand_with(book, work, Ptr::new(VRR, 0))?;
+      while !net.acts.is_empty() {
+        while !net.acts.is_empty() {
+          if let Some(outcome) = meter.spent(net) {
+            return Ok(outcome);
+          }
+          let done = net.reduce_upto(book, work, meter);
+          net.debug_validate();
+          done?;
+        }
+        net.expand_with(book, work, Ptr::new(VRR, 0))?;
+      }
+      return Ok(Outcome::Normal);
+    });
+  }
+
+  // Runs a reduction within a budget, given how it ended by 'run'. The node budget lowers 'limit'
+  // while it runs, and running out of memory becomes an outcome, instead of an error.
+  pub(crate) fn spend(&mut self, budget: Budget, run: impl FnOnce(&mut Net, &Meter) -> Result<Outcome, RuntimeError>) -> Result<(Outcome, Stats), RuntimeError> {
+    let limit = self.limit;
+    self.limit = limit.min(budget.node.unwrap_or(usize::MAX));
+    let done = run(self, &budget.start());
+    self.limit = limit;
+    return match done {
+      Ok(outcome) => Ok((outcome, self.stats)),
+      Err(RuntimeError::OutOfMemory { .. }) => Ok((Outcome::OutOfMemory, self.stats)),
+      Err(err) => Err(err),
+    };
+  }
+
+  // Reduces the redexes that the root depends on, keeping the others on 'acts'. Returns the number
+  // of redexes reduced.
+  pub fn reduce_lazy<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>) -> Result<usize, RuntimeError> {
+    return self.reduce_lazy_upto(book, work, &Budget::default().start());
+  }
+
+  // Same as 'reduce_lazy', stopping as 'reduce_upto' does.
+  fn reduce_lazy_upto<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, meter: &Meter) -> Result<usize, RuntimeError> {
+    let need = self.needed(&mut work.owns);
+    let mut idle = vec![];
+    for (i, act) in std::mem::take(&mut self.acts).into_iter().enumerate() {
+      if need[i] {
+        self.acts.push(act);
+      } else {
+        idle.push(act);
       }
-      self.expand(book, Ptr::new(VRR, 0));
     }
+    let done = self.reduce_upto(book, work, meter);
+    self.acts.append(&mut idle);
+    self.debug_validate();
+    return done;
+  }
+
+  // Reduces all redexes that the root depends on until there is none. Redexes that can't affect
+  // the result, such as those building unused structures, are left on 'acts'.
+  pub fn normal_lazy(&mut self, book: &Book, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    let work = &mut Worker::new(book);
+    return self.spend(budget, |net, meter| {
+      loop {
+        net.expand_with(book, work, Ptr::new(VRR, 0))?;
+        if let Some(outcome) = meter.spent(net) {
+          return Ok(outcome);
+        }
+        if net.reduce_lazy_upto(book, work, meter)? == 0 {
+          return Ok(Outcome::Normal);
+        }
+      }
+    });
+  }
+
+  // Reduces the net to weak head normal form, where the root is connected to a main port, of a
+  // node, number or eraser. Only the redex holding the head is reduced, one at a time, so nothing
+  // below the head, nor elsewhere, is reduced.
+  pub fn whnf(&mut self, book: &Book, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    let work = &mut Worker::new(book);
+    return self.spend(budget, |net, meter| {
+      loop {
+        if net.root.is_ref() {
+          net.expand_with(book, work, Ptr::new(VRR, 0))?;
+          continue;
+        }
+        if !net.root.is_var() {
+          return Ok(Outcome::Normal);
+        }
+        let Some(i) = net.holder(net.root.loc()) else {
+          return Ok(Outcome::Normal);
+        };
+        if let Some(outcome) = meter.spent(net) {
+          return Ok(outcome);
+        }
+        let (mut a, mut b) = net.acts[i];
+        net.room_for(book.demand(a, b)?)?;
+        net.acts.swap_remove(i);
+        net.interact(book, work, &mut a, &mut b)?;
+      }
+    });
+  }
+
+  // Finds the redexes that the root depends on: those connected by a wire to the root tree, or to
+  // another such redex. Returns whether each redex on 'acts' is needed. Uses 'owns' as scratch.
+  fn needed(&self, owns: &mut Vec<usize>) -> Vec<bool> {
+    // Maps each node to the redex whose trees hold it. Other nodes are on the root tree.
+    owns.clear();
+    owns.resize(self.node.len(), usize::MAX);
+    let mut todo = vec![];
+    for (i, &(a, b)) in self.acts.iter().enumerate() {
+      todo.push(a);
+      todo.push(b);
+      while let Some(ptr) = todo.pop() {
+        if ptr.is_nod() {
+          owns[ptr.loc() as usize] = i;
+          todo.push(self.get(ptr.loc(), P1));
+          todo.push(self.get(ptr.loc(), P2));
+        }
+      }
+    }
+    // Walks the trees reachable from the root, crossing wires to the redexes holding their ends.
+    let mut need = vec![false; self.acts.len()];
+    todo.push(self.root);
+    while let Some(ptr) = todo.pop() {
+      if ptr.is_nod() {
+        todo.push(self.get(ptr.loc(), P1));
+        todo.push(self.get(ptr.loc(), P2));
+      } else if ptr.is_var() && ptr.tag() != VRR {
+        let i = owns[ptr.loc() as usize];
+        if i != usize::MAX && !need[i] {
+          need[i] = true;
+          todo.push(self.acts[i].0);
+          todo.push(self.acts[i].1);
+        }
+      }
+    }
+    return need;
+  }
+
+  // Finds the redex whose trees hold the node at given location. Searches the newest redexes
+  // first, since the head is usually on one that was just created.
+  fn holder(&self, loc: Val) -> Option<usize> {
+    let mut todo = vec![];
+    for i in (0 .. self.acts.len()).rev() {
+      todo.push(self.acts[i].0);
+      todo.push(self.acts[i].1);
+      while let Some(ptr) = todo.pop() {
+        if ptr.is_nod() {
+          if ptr.loc() == loc {
+            return Some(i);
+          }
+          todo.push(self.get(ptr.loc(), P1));
+          todo.push(self.get(ptr.loc(), P2));
+        }
+      }
+    }
+    return None;
   }
 
   // Expands heads.
-  pub fn expand(&mut self, book: &Book, dir: Ptr) {
-    let ptr = *dir.target(self).unwrap();
-    if ptr.is_ctr() {
-      self.expand(book, Ptr::new(VR1, ptr.val()));
-      self.expand(book, Ptr::new(VR2, ptr.val()));
-    } else if ptr.is_ref() {
-      *dir.target(self).unwrap() = self.deref(book, ptr, dir, &mut 0);
+  pub fn expand(&mut self, book: &Book, dir: Ptr) -> Result<(), RuntimeError> {
+    return self.expand_with(book, &mut Worker::new(book), dir);
+  }
+
+  // Same as 'expand', with a given worker. Walks the tree on a stack of its own, as trees can be
+  // deeper than the call stack.
+  fn expand_with<O: ReductionObserver>(&mut self, book: &Book, work: &mut Worker<O>, dir: Ptr) -> Result<(), RuntimeError> {
+    let mut todo = vec![dir];
+    while let Some(dir) = todo.pop() {
+      let ptr = *dir.target(self).unwrap();
+      if ptr.is_ctr() {
+        todo.push(Ptr::new(VR2, ptr.loc()));
+        todo.push(Ptr::new(VR1, ptr.loc()));
+      } else if ptr.is_ref() {
+        self.room_for(book.expansion(ptr)?)?;
+        *dir.target(self).unwrap() = self.deref(book, work, ptr, dir)?;
+      }
     }
+    return Ok(());
   }
 
 }
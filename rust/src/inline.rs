@@ -0,0 +1,205 @@
+// Inlining of small definitions
+// =============================
+//
+// This file implements a pass over a book that replaces REFs to small definitions by copies of
+// their nets, so that the runtime doesn't have to expand them. Wrappers like 'dec' or 'low' are
+// expanded on every call, each costing a 'deref'; once inlined, their nodes come along with the
+// definition that refers to them, at the price of a bigger book.
+//
+// Two rules keep it from changing what a program does. A definition on a recursive strongly
+// connected component of REFs is never inlined, nor is one into its own component, so recursion
+// is never unrolled, and the pass always ends. And a REF is only inlined where it would be
+// expanded as soon as its caller is: on the root, or on a redex with anything other than an
+// eraser. Elsewhere, such as on a branch of a match, the REF may be erased without being
+// expanded, and a copy of its net would be built and then erased for nothing, or run redexes that
+// the program never needed, and that may never end. For the same reason, a net whose root is a
+// variable isn't inlined facing a REF: the REF would be linked to a port of one of its nodes,
+// rather than being expanded along with it.
+
+use crate::core::*;
+
+// The state of an inlining pass. It has:
+// - size: the most nodes a definition may have to be inlined.
+// - comp: the strongly connected component of each id, as an index into those of the book.
+// - recursive: whether each id is on a recursive component.
+struct Inliner {
+  size: usize,
+  comp: Vec<usize>,
+  recursive: Vec<bool>,
+}
+
+// The state of a search of strongly connected components, by Tarjan's algorithm. It has:
+// - index: the order in which each id was visited, if it was.
+// - low: the earliest visited id that each one reaches, among those still on the stack.
+// - held: whether each id is on the stack.
+// - stack: the visited ids whose component isn't complete yet.
+// - comps: the components found, each after those it refers to.
+// - next: the order of the next id visited.
+struct Search {
+  index: Vec<Option<usize>>,
+  low: Vec<usize>,
+  held: Vec<bool>,
+  stack: Vec<Val>,
+  comps: Vec<Vec<Val>>,
+  next: usize,
+}
+
+impl Book {
+  // Inlines every REF to a definition with at most 'size' nodes, after inlining into it in turn.
+  // Returns how many REFs were replaced.
+  pub fn inline(&mut self, size: usize) -> usize {
+    let comps = self.components();
+    let mut comp = vec![0; self.defs.len()];
+    for (index, ids) in comps.iter().enumerate() {
+      for &id in ids {
+        comp[id as usize] = index;
+      }
+    }
+    let recursive = self.recursive(&comps);
+    let pass = Inliner { size, comp, recursive };
+    let mut done = 0;
+    for ids in &comps {
+      for &id in ids {
+        if let Some(mut def) = self.undef(id) {
+          done += pass.inline_def(self, &mut def, pass.comp[id as usize]);
+          self.def(id, def);
+        }
+      }
+    }
+    return done;
+  }
+
+  // Splits the definitions in strongly connected components of their REFs, in which each one can
+  // reach every other. Every component comes after the ones it refers to.
+  pub(crate) fn components(&self) -> Vec<Vec<Val>> {
+    let mut search = Search {
+      index: vec![None; self.defs.len()],
+      low: vec![0; self.defs.len()],
+      held: vec![false; self.defs.len()],
+      stack: vec![],
+      comps: vec![],
+      next: 0,
+    };
+    for (id, _) in self.iter() {
+      if search.index[id as usize].is_none() {
+        self.connect(&mut search, id);
+      }
+    }
+    return search.comps;
+  }
+
+  // Tells whether each id is on a recursive component: one with many definitions, or with a
+  // single definition that refers to itself.
+  pub(crate) fn recursive(&self, comps: &[Vec<Val>]) -> Vec<bool> {
+    let mut recursive = vec![false; self.defs.len()];
+    for ids in comps {
+      for &id in ids {
+        recursive[id as usize] = ids.len() > 1 || refs(self.get(id).unwrap()).any(|other| other == id);
+      }
+    }
+    return recursive;
+  }
+
+  // Visits a definition, and the ones it refers to that weren't visited yet, completing the
+  // components that they start.
+  fn connect(&self, search: &mut Search, id: Val) {
+    let at = id as usize;
+    search.index[at] = Some(search.next);
+    search.low[at] = search.next;
+    search.next += 1;
+    search.held[at] = true;
+    search.stack.push(id);
+    for other in refs(self.get(id).unwrap()) {
+      if self.get(other).is_none() {
+        continue;
+      }
+      match search.index[other as usize] {
+        None => {
+          self.connect(search, other);
+          search.low[at] = search.low[at].min(search.low[other as usize]);
+        }
+        Some(index) if search.held[other as usize] => {
+          search.low[at] = search.low[at].min(index);
+        }
+        Some(_) => {}
+      }
+    }
+    if Some(search.low[at]) == search.index[at] {
+      let from = search.stack.iter().rposition(|&other| other == id).unwrap();
+      let comp = search.stack.split_off(from);
+      for &other in &comp {
+        search.held[other as usize] = false;
+      }
+      search.comps.push(comp);
+    }
+  }
+}
+
+// The ids of the REFs of a definition, on its root, redexes and nodes.
+pub(crate) fn refs(def: &Def) -> impl Iterator<Item = Val> + '_ {
+  let ports = def.node.iter().flat_map(|node| node.ports);
+  let acts = def.acts.iter().flat_map(|&(a, b)| [a, b]);
+  return [def.root].into_iter().chain(acts).chain(ports).filter(|ptr| ptr.is_ref()).map(|ptr| ptr.val());
+}
+
+impl Inliner {
+  // Inlines the REFs on the redexes and root of a definition from the component 'own', other than
+  // those of the nets it inlines, which were already done. Returns how many REFs were replaced.
+  fn inline_def(&self, book: &Book, def: &mut Def, own: usize) -> usize {
+    let mut done = 0;
+    // Redexes. If the root of an inlined net is a variable, the other side is linked to it.
+    for (mut a, mut b) in std::mem::take(&mut def.acts) {
+      if let Some(got) = self.inlinable(book, a, b, own) {
+        a = embed(def, got, b);
+        done += 1;
+      }
+      if !a.is_var() {
+        if let Some(got) = self.inlinable(book, b, a, own) {
+          b = embed(def, got, a);
+          done += 1;
+        }
+      }
+      if !a.is_var() && !b.is_var() {
+        def.acts.push((a, b));
+      }
+    }
+    // Root, which 'deref' follows right away if it is a REF.
+    if let Some(got) = self.inlinable(book, def.root, Ptr::new(VRR, 0), own) {
+      def.root = embed(def, got, Ptr::new(VRR, 0));
+      done += 1;
+    }
+    return done;
+  }
+
+  // Gets the definition that a pointer should be replaced by, if it is a REF that can be inlined
+  // where it is, facing 'other'. A REF facing an eraser is collected without being expanded, and
+  // one facing a net whose root is a variable would be linked to its node unexpanded.
+  fn inlinable<'a>(&self, book: &'a Book, ptr: Ptr, other: Ptr, own: usize) -> Option<&'a Def> {
+    if !ptr.is_ref() || other.is_era() {
+      return None;
+    }
+    let got = book.get(ptr.val())?;
+    let id = ptr.val() as usize;
+    if got.root.is_var() && other.is_ref() {
+      return None;
+    }
+    if got.node.len() <= self.size && got.root.tag() != VRR && self.comp[id] != own && !self.recursive[id] {
+      return Some(got);
+    }
+    return None;
+  }
+}
+
+// Appends the nodes and redexes of a definition to another, and returns its root, adjusted. The
+// port that its root links to is linked to 'parent' instead.
+fn embed(def: &mut Def, got: &Def, parent: Ptr) -> Ptr {
+  let locs : Vec<Val> = (def.node.len() .. def.node.len() + got.node.len()).map(|loc| loc as Val).collect();
+  let place = |ptr: Ptr| if ptr.tag() == VRR { parent } else { ptr.adjust(&locs) };
+  for node in &got.node {
+    def.node.push(Node::new(place(*node.port(P1)), place(*node.port(P2)), node.lab));
+  }
+  for &(a, b) in &got.acts {
+    def.acts.push((place(a), place(b)));
+  }
+  return place(got.root);
+}
@@ -9,19 +9,22 @@
 // <net>    ::= <root> <acts>
 //   <root> ::= "$" <tree>
 //   <acts> ::= "&" <tree> "~" <tree> <net>
-// <tree>   ::= <era> | <nod> | <var> | <num>
+// <tree>   ::= <era> | <nod> | <op2> | <mat> | <var> | <num>
 //   <era>  ::= "*"
 //   <nod>  ::= "(" <num_lit> " " <tree> " " <tree> ")"
+//   <op2>  ::= "<" [":"] <tree> " " <tree> ">"
+//   <mat>  ::= "?<" <tree> " " <tree> ">"
 //   <var>  ::= <str_lit>
-//   <num>  ::= <num_lit>
-//   <ref>  ::= "@" <str_lit>
+//   <num>  ::= <num_lit> | "[" <opr> [<num_lit>] "]"
+//   <ref>  ::= "@" <str_lit> | "@#" <num_lit>
 //
 // For example, below is the church nat 2, encoded as an interaction net:
 //
 // $ (0 (1 (0 b a) (0 a R)) (0 b R))
 //
 // The '$' symbol denotes the net's root. A node is denoted as `(LABEL CHILD_1 CHILD_2)`.
-// The label 0 is used for CON nodes, while labels >1 are used for DUP nodes. A node has two
+// The label 0 is used for CON nodes, while labels >0 are used for DUP nodes, up to 2^32 - 1 (or
+// 2^64 - 1 with 'ptr64'). Nodes with equal labels annihilate, while others commute. A node has two
 // children, representing Port1->Port0 and Port2->Port0 wires. Variables are denoted by
 // alphanumeric names, and used to represent auxiliary wires (Port1->Port2 and Port2->Port1).
 // Active wires (Port0->Port0) are represented with by '& left_tree ~ right_tree'. For example:
@@ -30,9 +33,28 @@
 // ~ (0 y y)
 //
 // The net above represents two identity CON nodes connected by their main ports. This net has no
-// root, so it will just reduce to nothingness. Numbers are represented by numeric literals.
-// References (to closed nets) are denoted by '@name', where the name must have at most 5 letters,
-// from the following alphabet: ".0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_".
+// root, so it will just reduce to nothingness. Numbers are represented by numeric literals, from 0
+// to NUM_MASK (2^24 - 1, or 2^52 - 1 with 'ptr64'); larger ones are a parse error. They can also
+// carry an operator, as in '[+1]', or be an operator alone, as in '[+]'. A numeric
+// operation is denoted by '<RIGHT RESULT>': its main port takes the left operand. For example,
+// below is a net that computes 5 - 1:
+//
+// $ r
+// & 5 ~ <[-1] r>
+//
+// While waiting for a right operand that isn't a number yet, the node holds the left one, and is
+// shown as '<:LEFT RESULT>'. The available operators are: + - * / % == != < > & | ^ << >>.
+// A numeric match is denoted by '?<(0 ZERO SUCC) RESULT>': its main port takes a number, and
+// the branch that doesn't apply is erased. ZERO is connected to the result, while SUCC receives
+// the predecessor and the result, as '(0 PRED RESULT)'. For example, below is a net that computes
+// the predecessor of 5, or 0 for 0:
+//
+// $ r
+// & 5 ~ ?<(0 0 (0 p p)) r>
+//
+// References (to closed nets) are denoted by '@name', where the name is made of letters, digits,
+// '_' and '.'. A book interns each name to an id, which is what the runtime sees. When there is no
+// name for an id, it is shown as '@#id', which is parsed back as a REF to that id.
 
 use crate::core::*;
 use std::collections::HashMap;
@@ -48,6 +70,7 @@ pub enum LTree {
   Era,
   Nod { 
     tag: Tag,
+    lab: Lab,
     lft: Box<LTree>,
     rgt: Box<LTree>,
   },
@@ -55,8 +78,9 @@ pub enum LTree {
     nam: String,
   },
   Ref {
-    nam: Val
+    nam: String
   },
+  #[allow(clippy::upper_case_acronyms)]
   NUM {
     val: Val
   },
@@ -66,17 +90,21 @@ impl Debug for LTree {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     match self {
       Self::Era => write!(f, "Era"),
-      Self::Nod { tag, lft, rgt } => f
+      Self::Nod { tag, lab, lft, rgt } => f
         .debug_struct("Node")
         .field(
           "tag",
           match *tag {
-            CON => &"CON",
-            DUP => &"DUP",
+            CTR if *lab == CON => &"CON",
+            CTR => &"DUP",
+            OP2 => &"OP2",
+            OP1 => &"OP1",
+            MAT => &"MAT",
             ERA => &"ERA",
             _ => &"???",
           },
         )
+        .field("lab", lab)
         .field("lft", lft)
         .field("rgt", rgt)
         .finish(),
@@ -87,6 +115,26 @@ impl Debug for LTree {
   }
 }
 
+// Trees are dropped on a stack of their own, as they can be deeper than the call stack.
+impl Drop for LTree {
+  fn drop(&mut self) {
+    fn take(tree: &mut LTree, todo: &mut Vec<LTree>) {
+      if let LTree::Nod { lft, rgt, .. } = tree {
+        for sub in [lft, rgt] {
+          if let LTree::Nod { .. } = **sub {
+            todo.push(std::mem::replace(&mut **sub, LTree::Era));
+          }
+        }
+      }
+    }
+    let mut todo = vec![];
+    take(self, &mut todo);
+    while let Some(mut tree) = todo.pop() {
+      take(&mut tree, &mut todo);
+    }
+  }
+}
+
 type LActs = Vec<(LTree,LTree)>;
 
 #[derive(Debug, Clone)]
@@ -98,6 +146,43 @@ pub struct LNet {
 // Parser
 // ------
 
+// A reason a net couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+  // A character other than the expected one, or the end of the code, if 'got' is none.
+  Expected { want: char, got: Option<char> },
+  // An operator that doesn't exist.
+  BadOperator { sym: String },
+  // A number literal larger than the most it may be, such as NUM_MASK for a number.
+  BigNumber { text: String, max: Val },
+  // A name that can't be interned on the book, as a definition or as a REF.
+  BadSymbol(SymbolError),
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParseError::Expected { want, got: Some(got) } => {
+        write!(f, "expected '{}', found '{}'", want, got)
+      }
+      ParseError::Expected { want, got: None } => {
+        write!(f, "expected '{}', found the end of the code", want)
+      }
+      ParseError::BadOperator { sym } => {
+        write!(f, "invalid operator: '{}'", sym)
+      }
+      ParseError::BigNumber { text, max } => {
+        write!(f, "number too big: {} is larger than {}", text, max)
+      }
+      ParseError::BadSymbol(err) => {
+        write!(f, "{}", err)
+      }
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
 fn skip_spaces(chars: &mut Peekable<Chars>) {
   while let Some(c) = chars.peek() {
     if *c != ' ' && *c != '\n' {
@@ -107,24 +192,34 @@ fn skip_spaces(chars: &mut Peekable<Chars>) {
   }
 }
 
-fn consume(chars: &mut Peekable<Chars>, text: &str) {
+fn consume(chars: &mut Peekable<Chars>, text: &str) -> Result<(), ParseError> {
   skip_spaces(chars);
-  for c in text.chars() {
-    assert_eq!(chars.next().unwrap(), c);
+  for want in text.chars() {
+    let got = chars.next();
+    if got != Some(want) {
+      return Err(ParseError::Expected { want, got });
+    }
   }
+  Ok(())
 }
 
-pub fn parse_num_lit(chars: &mut Peekable<Chars>) -> u32 {
-  let mut num : u32 = 0;
+// Parses a number literal, failing if it is larger than 'max'.
+pub fn parse_num_lit(chars: &mut Peekable<Chars>, max: Val) -> Result<Val, ParseError> {
+  let mut text = String::new();
+  let mut num : Option<Val> = Some(0);
   skip_spaces(chars);
   while let Some(c) = chars.peek() {
-    if !c.is_digit(10) {
+    if !c.is_ascii_digit() {
       break;
     }
-    num = num * 10 + c.to_digit(10).unwrap() as u32;
+    text.push(*c);
+    num = num.and_then(|num| num.checked_mul(10)?.checked_add(c.to_digit(10).unwrap() as Val));
     chars.next();
   }
-  num
+  match num {
+    Some(num) if num <= max => Ok(num),
+    _ => Err(ParseError::BigNumber { text, max }),
+  }
 }
 
 pub fn parse_str_lit(chars: &mut Peekable<Chars>) -> String {
@@ -140,84 +235,191 @@ pub fn parse_str_lit(chars: &mut Peekable<Chars>) -> String {
   str
 }
 
-pub fn parse_ltree(chars: &mut Peekable<Chars>) -> LTree {
+pub fn parse_opr(chars: &mut Peekable<Chars>) -> Result<Val, ParseError> {
+  let mut sym = String::new();
   skip_spaces(chars);
-  match chars.peek() {
+  while let Some(c) = chars.peek() {
+    if !"+-*/%=!<>&|^".contains(*c) {
+      break;
+    }
+    sym.push(*c);
+    chars.next();
+  }
+  Ok(match sym.as_str() {
+    "+"  => ADD,
+    "-"  => SUB,
+    "*"  => MUL,
+    "/"  => DIV,
+    "%"  => MOD,
+    "==" => EQ,
+    "!=" => NE,
+    "<"  => LT,
+    ">"  => GT,
+    "&"  => AND,
+    "|"  => OR,
+    "^"  => XOR,
+    "<<" => SHL,
+    ">>" => SHR,
+    _    => return Err(ParseError::BadOperator { sym }),
+  })
+}
+
+pub fn parse_ltree(chars: &mut Peekable<Chars>) -> Result<LTree, ParseError> {
+  skip_spaces(chars);
+  Ok(match chars.peek() {
     Some('*') => {
       chars.next();
       LTree::Era
     },
     Some('(') => {
       chars.next();
-      let tag = CON + (parse_num_lit(chars) as u16);
-      let lft = Box::new(parse_ltree(chars));
-      let rgt = Box::new(parse_ltree(chars));
-      consume(chars, ")");
-      LTree::Nod { tag, lft, rgt }
+      let lab = parse_num_lit(chars, Lab::MAX)?;
+      let lft = Box::new(parse_ltree(chars)?);
+      let rgt = Box::new(parse_ltree(chars)?);
+      consume(chars, ")")?;
+      LTree::Nod { tag: CTR, lab, lft, rgt }
+    },
+    Some('<') => {
+      chars.next();
+      let tag = if chars.peek() == Some(&':') { chars.next(); OP1 } else { OP2 };
+      let lft = Box::new(parse_ltree(chars)?);
+      let rgt = Box::new(parse_ltree(chars)?);
+      consume(chars, ">")?;
+      LTree::Nod { tag, lab: 0, lft, rgt }
+    },
+    Some('?') => {
+      chars.next();
+      consume(chars, "<")?;
+      let lft = Box::new(parse_ltree(chars)?);
+      let rgt = Box::new(parse_ltree(chars)?);
+      consume(chars, ">")?;
+      LTree::Nod { tag: MAT, lab: 0, lft, rgt }
+    },
+    Some('[') => {
+      chars.next();
+      let opr = parse_opr(chars)?;
+      skip_spaces(chars);
+      let val = if chars.peek() == Some(&']') { None } else { Some(parse_num_lit(chars, NUM_MASK)?) };
+      consume(chars, "]")?;
+      match val {
+        Some(val) => LTree::NUM { val: Ptr::new_num(opr, val).val() },
+        None      => LTree::NUM { val: Ptr::new_num(OPR, opr).val() },
+      }
     },
     Some('@') => {
       chars.next();
       skip_spaces(chars);
-      let name = parse_str_lit(chars);
-      LTree::Ref { nam: name_to_u32(&name) }
+      if chars.peek() == Some(&'#') {
+        chars.next();
+        LTree::Ref { nam: format!("#{}", parse_num_lit(chars, VAL_MASK)?) }
+      } else {
+        LTree::Ref { nam: parse_str_lit(chars) }
+      }
     },
-    Some(c) if c.is_digit(10) => {
-      LTree::NUM { val: parse_num_lit(chars) }
+    Some(c) if c.is_ascii_digit() => {
+      LTree::NUM { val: Ptr::new_num(USE, parse_num_lit(chars, NUM_MASK)?).val() }
     },
     _ => {
       LTree::Var { nam: parse_str_lit(chars) }
     },
-  }
+  })
 }
 
-pub fn parse_lnet(chars: &mut Peekable<Chars>) -> LNet {
+pub fn parse_lnet(chars: &mut Peekable<Chars>) -> Result<LNet, ParseError> {
   let mut acts = Vec::new();
   let mut root = LTree::Era;
   while let Some(c) = { skip_spaces(chars); chars.peek() } {
     if *c == '$' {
       chars.next();
-      root = parse_ltree(chars);
+      root = parse_ltree(chars)?;
     } else if *c == '&' {
       chars.next();
-      let tree1 = parse_ltree(chars);
-      consume(chars, "~");
-      let tree2 = parse_ltree(chars);
+      let tree1 = parse_ltree(chars)?;
+      consume(chars, "~")?;
+      let tree2 = parse_ltree(chars)?;
       acts.push((tree1, tree2));
     } else {
       break;
     }
   }
-  LNet { root, acts }
+  Ok(LNet { root, acts })
 }
 
-pub fn do_parse_ltree(code: &str) -> LTree {
+pub fn do_parse_ltree(code: &str) -> Result<LTree, ParseError> {
   parse_ltree(&mut code.chars().peekable())
 }
 
-pub fn do_parse_lnet(code: &str) -> LNet {
+pub fn do_parse_lnet(code: &str) -> Result<LNet, ParseError> {
   parse_lnet(&mut code.chars().peekable())
 }
 
 // Stringifier
 // -----------
 
+// Shows a tree. Walks it on a stack of its own, as trees can be deeper than the call stack.
 pub fn show_ltree(tree: &LTree) -> String {
-  match tree {
-    LTree::Era => {
-      "*".to_string()
-    },
-    LTree::Nod { tag, lft, rgt } => {
-      format!("({} {} {})", tag - CON, show_ltree(&*lft), show_ltree(&*rgt))
-    },
-    LTree::Var { nam } => {
-      nam.clone()
-    },
-    LTree::Ref { nam } => {
-      format!("@{}", u32_to_name(*nam))
-    },
-    LTree::NUM { val } => {
-      val.to_string()
-    },
+  enum Part<'a> { Tree(&'a LTree), Text(&'static str) }
+  let mut text = String::new();
+  let mut todo = vec![Part::Tree(tree)];
+  while let Some(part) = todo.pop() {
+    match part {
+      Part::Text(str) => {
+        text.push_str(str);
+      },
+      Part::Tree(LTree::Nod { tag, lab, lft, rgt }) => {
+        match *tag {
+          OP2 => text.push('<'),
+          OP1 => text.push_str("<:"),
+          MAT => text.push_str("?<"),
+          _   => text.push_str(&format!("({} ", lab)),
+        }
+        todo.push(Part::Text(if *tag == CTR { ")" } else { ">" }));
+        todo.push(Part::Tree(rgt));
+        todo.push(Part::Text(" "));
+        todo.push(Part::Tree(lft));
+      },
+      Part::Tree(LTree::Era) => {
+        text.push('*');
+      },
+      Part::Tree(LTree::Var { nam }) => {
+        text.push_str(nam);
+      },
+      Part::Tree(LTree::Ref { nam }) => {
+        text.push_str(&format!("@{}", nam));
+      },
+      Part::Tree(LTree::NUM { val }) => {
+        text.push_str(&show_num(Ptr::new(NUM, *val)));
+      },
+    }
+  }
+  text
+}
+
+pub fn show_opr(opr: Val) -> &'static str {
+  match opr {
+    ADD => "+",
+    SUB => "-",
+    MUL => "*",
+    DIV => "/",
+    MOD => "%",
+    EQ  => "==",
+    NE  => "!=",
+    LT  => "<",
+    GT  => ">",
+    AND => "&",
+    OR  => "|",
+    XOR => "^",
+    SHL => "<<",
+    SHR => ">>",
+    _   => "?",
+  }
+}
+
+pub fn show_num(num: Ptr) -> String {
+  match num.opr() {
+    USE => num.num().to_string(),
+    OPR => format!("[{}]", show_opr(num.num())),
+    opr => format!("[{}{}]", show_opr(opr), num.num()),
   }
 }
 
@@ -230,8 +432,14 @@ pub fn show_lnet(lnet: &LNet) -> String {
   return result;
 }
 
+// Shows a net, with REFs shown as '@#id', since it has no names for them.
 pub fn show_net(net: &Net) -> String {
-  show_lnet(&readback_lnet(net))
+  show_net_with(net, &Symbols::default())
+}
+
+// Shows a net, naming its REFs by the given symbols, such as those of its book.
+pub fn show_net_with(net: &Net, syms: &Symbols) -> String {
+  show_lnet(&readback_lnet(net, syms))
 }
 
 // Conversion
@@ -264,74 +472,19 @@ pub fn port_to_tag(port: Port) -> Tag {
   }
 }
 
-pub fn lnet_to_net(lnet: &LNet, padding_factor: usize) -> Net {
+// Builds a runtime net, interning the names of its REFs on 'syms'. Fails if one can't be.
+pub fn lnet_to_net(lnet: &LNet, padding_factor: usize, syms: &mut Symbols) -> Result<Net, SymbolError> {
   let mut vars = HashMap::new();
-  let mut net = Net::new(1 << 16);
-  net.root = alloc_ltree(&mut net, &lnet.root, &mut vars, Parent::Root);
+  let mut net = Net::new(usize::MAX);
+  net.root = alloc_ltree(&mut net, &lnet.root, &mut vars, Parent::Root, syms)?;
   for (tree1, tree2) in &lnet.acts {
-    let ptr1 = alloc_ltree(&mut net, tree1, &mut vars, Parent::Acts);
-    let ptr2 = alloc_ltree(&mut net, tree2, &mut vars, Parent::Acts);
+    let ptr1 = alloc_ltree(&mut net, tree1, &mut vars, Parent::Acts, syms)?;
+    let ptr2 = alloc_ltree(&mut net, tree2, &mut vars, Parent::Acts, syms)?;
     net.acts.push((ptr1, ptr2));
   }
-  net.node = net.node[0 .. net.used * padding_factor].to_vec();
-  return net;
-}
-
-pub fn name_to_letters(name: &str) -> Vec<u8> {
-  let mut letters = Vec::new();
-  for c in name.chars() {
-    letters.push(match c {
-      '.'       => 0,
-      '0'..='9' => c as u8 - '0' as u8 + 1,
-      'A'..='Z' => c as u8 - 'A' as u8 + 11,
-      'a'..='z' => c as u8 - 'a' as u8 + 37,
-      '_'       => 63,
-      _         => panic!("Invalid character in name"),
-    });
-  }
-  return letters;
-}
-
-pub fn letters_to_name(letters: Vec<u8>) -> String {
-  let mut name = String::new();
-  for letter in letters {
-    name.push(match letter {
-            0 => '.',
-       1..=10 => (letter - 1 + '0' as u8) as char,
-      11..=36 => (letter - 11 + 'A' as u8) as char,
-      37..=62 => (letter - 37 + 'a' as u8) as char,
-           63 => '_',
-      _       => panic!("Invalid letter in name"),
-    });
-  }
-  return name;
-}
-
-pub fn u32_to_letters(num: u32) -> Vec<u8> {
-  let mut letters = Vec::new();
-  let mut num = num;
-  while num > 0 {
-    letters.push((num % 64) as u8);
-    num /= 64;
-  }
-  letters.reverse();
-  return letters;
-}
-
-pub fn letters_to_u32(letters: Vec<u8>) -> u32 {
-  let mut num = 0;
-  for letter in letters {
-    num = num * 64 + letter as u32;
-  }
-  return num;
-}
-
-pub fn name_to_u32(name: &str) -> u32 {
-  letters_to_u32(name_to_letters(name))
-}
-
-pub fn u32_to_name(num: u32) -> String {
-  letters_to_name(u32_to_letters(num))
+  net.node.resize(net.used * padding_factor, Node::nil());
+  net.reclaim();
+  return Ok(net);
 }
 
 // Injection and Readback
@@ -344,16 +497,19 @@ pub enum Parent {
   Node { val: Val, port: Port },
 }
 
-pub fn alloc_ltree(net: &mut Net, tree: &LTree, vars: &mut HashMap<String, Parent>, parent: Parent) -> Ptr {
-  match tree {
+// Allocates a tree on a net, interning the names of its REFs on 'syms', or taking the id of those
+// named '#id'. Fails if a name can't be interned.
+pub fn alloc_ltree(net: &mut Net, tree: &LTree, vars: &mut HashMap<String, Parent>, parent: Parent, syms: &mut Symbols) -> Result<Ptr, SymbolError> {
+  Ok(match tree {
     LTree::Era => {
       Ptr::new(ERA, 0)
     },
-    LTree::Nod { tag, lft, rgt } => {
+    LTree::Nod { tag, lab, lft, rgt } => {
       let val = net.alloc();
-      let p1 = alloc_ltree(net, &*lft, vars, Parent::Node { val, port: P1 });
+      net.at_mut(val).lab = *lab;
+      let p1 = alloc_ltree(net, lft, vars, Parent::Node { val, port: P1 }, syms)?;
       net.set(val, P1, p1);
-      let p2 = alloc_ltree(net, &*rgt, vars, Parent::Node { val, port: P2 });
+      let p2 = alloc_ltree(net, rgt, vars, Parent::Node { val, port: P2 }, syms)?;
       net.set(val, P2, p2);
       Ptr::new(*tag, val)
     },
@@ -377,7 +533,7 @@ pub fn alloc_ltree(net: &mut Net, tree: &LTree, vars: &mut HashMap<String, Paren
               net.root = Ptr::new(VRR, 0);
             }
           }
-          return Ptr::new(VRR, 0);
+          return Ok(Ptr::new(VRR, 0));
         },
         Some(Parent::Node { val: other_val, port: other_port }) => {
           //println!("linked {} | set {} {:?} as {} {:?}", nam, other_val, other_port, val, port);
@@ -392,7 +548,7 @@ pub fn alloc_ltree(net: &mut Net, tree: &LTree, vars: &mut HashMap<String, Paren
               net.set(*other_val, *other_port, Ptr::new(VRR, 0))
             }
           }
-          return Ptr::new(port_to_tag(*other_port), *other_val);
+          return Ok(Ptr::new(port_to_tag(*other_port), *other_val));
         }
         None => {
           //println!("linkin {} | iam {} {:?}", nam, val, port);
@@ -402,64 +558,86 @@ pub fn alloc_ltree(net: &mut Net, tree: &LTree, vars: &mut HashMap<String, Paren
       }
     },
     LTree::Ref { nam } => {
-      Ptr::new(REF, *nam)
+      let id = match nam.strip_prefix('#') {
+        Some(id) => id.parse().ok().filter(|&id| id <= VAL_MASK).ok_or_else(|| SymbolError::Invalid { name: nam.clone() })?,
+        None     => syms.intern(nam)?,
+      };
+      Ptr::new(REF, id)
     },
     LTree::NUM { val } => {
       Ptr::new(NUM, *val)
     },
-  }
+  })
 }
 
-pub fn do_alloc_ltree(net: &mut Net, tree: &LTree) -> Ptr {
-  alloc_ltree(net, tree, &mut HashMap::new(), Parent::Root)
+pub fn do_alloc_ltree(net: &mut Net, tree: &LTree, syms: &mut Symbols) -> Result<Ptr, SymbolError> {
+  alloc_ltree(net, tree, &mut HashMap::new(), Parent::Root, syms)
 }
 
-pub fn readback_ltree(net: &Net, ptr: Ptr, parent: Parent, vars: &mut HashMap<Parent,String>, fresh: &mut usize) -> LTree {
-  match ptr.tag() {
-    NIL => {
-      LTree::Var { nam: "?".to_string() }
-    },
-    ERA => {
-      LTree::Era
-    },
-    REF => {
-      LTree::Ref { nam: ptr.val() }
-    },
-    NUM => {
-      LTree::NUM { val: ptr.val() }
-    },
-    VRR | VR1 | VR2 => {
-      let key = match ptr.tag() {
-        VR1 => Parent::Node { val: ptr.val(), port: P1 },
-        VR2 => Parent::Node { val: ptr.val(), port: P2 },
-        VRR => Parent::Root,
-        _   => unreachable!(),
-      };
-      if let Some(nam) = vars.get(&key) {
-        LTree::Var { nam: nam.clone() }
-      } else {
-        let nam = num_to_str(*fresh);
-        *fresh += 1;
-        vars.insert(parent, nam.clone());
-        LTree::Var { nam }
-      }
-    },
-    _ => {
-      let lft = readback_ltree(net, net.get(ptr.val(), P1), Parent::Node { val: ptr.val(), port: P1 }, vars, fresh);
-      let rgt = readback_ltree(net, net.get(ptr.val(), P2), Parent::Node { val: ptr.val(), port: P2 }, vars, fresh);
-      LTree::Nod { tag: ptr.tag(), lft: Box::new(lft), rgt: Box::new(rgt) }
-    },
+// Reads back the tree on a pointer. Walks it on a stack of its own, as trees can be deeper than the
+// call stack: each node is visited, then its children, then built from the trees they left.
+pub fn readback_ltree(net: &Net, ptr: Ptr, parent: Parent, vars: &mut HashMap<Parent,String>, fresh: &mut usize, syms: &Symbols) -> LTree {
+  enum Task { Visit(Ptr, Parent), Build(Tag, Lab) }
+  let mut todo = vec![Task::Visit(ptr, parent)];
+  let mut done = vec![];
+  while let Some(task) = todo.pop() {
+    let (ptr, parent) = match task {
+      Task::Visit(ptr, parent) => (ptr, parent),
+      Task::Build(tag, lab) => {
+        let rgt = done.pop().unwrap();
+        let lft = done.pop().unwrap();
+        done.push(LTree::Nod { tag, lab, lft: Box::new(lft), rgt: Box::new(rgt) });
+        continue;
+      },
+    };
+    done.push(match ptr.tag() {
+      NIL => {
+        LTree::Var { nam: "?".to_string() }
+      },
+      ERA => {
+        LTree::Era
+      },
+      REF => {
+        LTree::Ref { nam: syms.show(ptr.val()) }
+      },
+      NUM => {
+        LTree::NUM { val: ptr.val() }
+      },
+      VRR | VR1 | VR2 => {
+        let key = match ptr.tag() {
+          VR1 => Parent::Node { val: ptr.loc(), port: P1 },
+          VR2 => Parent::Node { val: ptr.loc(), port: P2 },
+          VRR => Parent::Root,
+          _   => unreachable!(),
+        };
+        if let Some(nam) = vars.get(&key) {
+          LTree::Var { nam: nam.clone() }
+        } else {
+          let nam = num_to_str(*fresh);
+          *fresh += 1;
+          vars.insert(parent, nam.clone());
+          LTree::Var { nam }
+        }
+      },
+      _ => {
+        todo.push(Task::Build(ptr.tag(), net.at(ptr.loc()).lab));
+        todo.push(Task::Visit(net.get(ptr.loc(), P2), Parent::Node { val: ptr.loc(), port: P2 }));
+        todo.push(Task::Visit(net.get(ptr.loc(), P1), Parent::Node { val: ptr.loc(), port: P1 }));
+        continue;
+      },
+    });
   }
+  done.pop().unwrap()
 }
 
-pub fn readback_lnet(net: &Net) -> LNet {
+pub fn readback_lnet(net: &Net, syms: &Symbols) -> LNet {
   let mut vars = HashMap::new();
   let mut fresh = 0;
   let mut acts = Vec::new();
-  let root = readback_ltree(net, net.root, Parent::Root, &mut vars, &mut fresh);
+  let root = readback_ltree(net, net.root, Parent::Root, &mut vars, &mut fresh, syms);
   for &(a, b) in &net.acts {
-    let tree_a = readback_ltree(net, a, Parent::Acts, &mut vars, &mut fresh);
-    let tree_b = readback_ltree(net, b, Parent::Acts, &mut vars, &mut fresh);
+    let tree_a = readback_ltree(net, a, Parent::Acts, &mut vars, &mut fresh, syms);
+    let tree_b = readback_ltree(net, b, Parent::Acts, &mut vars, &mut fresh, syms);
     acts.push((tree_a, tree_b));
   }
   LNet { root, acts }
@@ -468,8 +646,17 @@ pub fn readback_lnet(net: &Net) -> LNet {
 // Utils
 // -----
 
-pub fn define(book: &mut Book, name: &str, code: &str) -> u32 {
-  let id = name_to_u32(name);
-  book.def(id, lnet_to_net(&do_parse_lnet(code), 1));
-  return id;
+// Defines a name on the book, from its textual syntax. Returns its id, or fails if the code can't
+// be parsed, or if the name, or that of one of its REFs, can't be interned.
+pub fn try_define(book: &mut Book, name: &str, code: &str) -> Result<Val, ParseError> {
+  let lnet = do_parse_lnet(code)?;
+  let id = book.syms.intern(name).map_err(ParseError::BadSymbol)?;
+  let net = lnet_to_net(&lnet, 1, &mut book.syms).map_err(ParseError::BadSymbol)?;
+  book.def(id, net.into());
+  return Ok(id);
+}
+
+// Same as 'try_define', panicking on failure, for code that is known to be valid.
+pub fn define(book: &mut Book, name: &str, code: &str) -> Val {
+  return try_define(book, name, code).unwrap_or_else(|err| panic!("{}", err));
 }
@@ -1,5 +1,13 @@
+#![allow(clippy::needless_return)]
+
 pub mod core;
+pub mod inline;
 pub mod lang;
+pub mod par;
+pub mod precompute;
+pub mod sched;
+pub mod snapshot;
+pub mod validate;
 
 pub use crate::core::*;
 pub use crate::lang::*;
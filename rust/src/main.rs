@@ -2,12 +2,20 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(clippy::needless_return)]
 
 mod core;
+mod inline;
 mod lang;
+mod par;
+mod precompute;
+mod sched;
+mod snapshot;
+mod validate;
 
 use crate::core::*;
 use crate::lang::*;
+use crate::snapshot::*;
 
 fn main() {
   // Initializes the book
@@ -218,19 +226,40 @@ fn main() {
     & @c15 ~ (0 @S (0 @Z dep))
   "); 
 
-  // Initializes the net
+  // Checks the book before running it
+  if let Err(errs) = book.check() {
+    for err in errs {
+      println!("error: {}", err);
+    }
+    return;
+  }
+
+  // Reduces the redexes of each definition ahead of time, then inlines the definitions of up to 8
+  // nodes into those that refer to them
+  book.precompute();
+  book.inline(8);
+
+  // Initializes the net, which can grow up to 2^24 nodes
   let net = &mut Net::new(1 << 24);
-  net.boot(name_to_u32("ex2"));
+  net.boot(book.syms.id("ex2").unwrap());
+  net.profile();
 
   // Computes its normal form
-  net.expand(book, Ptr::new(VRR,0));
-  net.normal(book, None);
+  let done = net.expand(book, Ptr::new(VRR,0)).and_then(|()| net.normal(book, Budget::default()));
+  match done {
+    Ok((outcome, _)) => println!("done: {:?}", outcome),
+    Err(err)         => println!("error: {}", err),
+  }
 
   //Shows results and stats
   //println!("[net]\n{}", show_net(&net));
   println!("size: {}", net.node.len());
   println!("used: {}", net.used);
   println!("rwts: {}", net.rwts);
+  println!("{}", net.stats);
+  if let Some(prof) = &net.prof {
+    println!("[profile]\n{}", prof.show(&book.syms));
+  }
 
   //println!("net.root = {:08x}", net.root.data);
   //for i in 0 .. net.acts.len() {
@@ -245,23 +274,31 @@ fn main() {
   //populate_cuda(&book); // prints CUDA book
 }
 
+// Converts a pointer of a definition to the CUDA runtime's layout, panicking if it has no
+// equivalent there.
+fn cuda_ptr(def: &Def, ptr: Ptr) -> u32 {
+  let lab = if ptr.is_ctr() { def.node[ptr.loc() as usize].lab } else { 0 };
+  return to_cuda(ptr, lab).unwrap_or_else(|| panic!("pointer {:?} doesn't fit the CUDA layout", ptr));
+}
+
 fn populate_cuda(book: &Book) {
   println!("Term* term;");
-  let mut sorted_defs: Vec<(&u32, &Net)> = book.defs.iter().collect();
-  sorted_defs.sort_by_key(|&(key, _)| key);
-  for (key, def) in sorted_defs {
-    println!("  // {}", u32_to_name(*key));
+  for (key, def) in book.iter() {
+    if key > 0xFFF_FFFF {
+      panic!("@{} has id {}, which doesn't fit the CUDA layout", book.syms.show(key), key);
+    }
+    println!("  // {}", book.syms.show(key));
     println!("  book->defs[0x{:08x}]           = (Term*) malloc(sizeof(Term));", key);
-    println!("  book->defs[0x{:08x}]->root     = 0x{:08x};", key, def.root.data);
+    println!("  book->defs[0x{:08x}]->root     = 0x{:08x};", key, cuda_ptr(def, def.root));
     println!("  book->defs[0x{:08x}]->alen     = {};", key, def.acts.len());
     println!("  book->defs[0x{:08x}]->acts     = (Wire*) malloc({} * sizeof(Wire));", key, def.acts.len());
     for i in 0..def.acts.len() {
-        println!("  book->defs[0x{:08x}]->acts[{:2}] = mkwire(0x{:08x},0x{:08x});", key, i, def.acts[i].0.data, def.acts[i].1.data);
+        println!("  book->defs[0x{:08x}]->acts[{:2}] = mkwire(0x{:08x},0x{:08x});", key, i, cuda_ptr(def, def.acts[i].0), cuda_ptr(def, def.acts[i].1));
     }
     println!("  book->defs[0x{:08x}]->nlen     = {};", key, def.node.len());
     println!("  book->defs[0x{:08x}]->node     = (Node*) malloc({} * sizeof(Node));", key, def.node.len());
     for i in 0..def.node.len() {
-      println!("  book->defs[0x{:08x}]->node[{:2}] = (Node) {{0x{:08x},0x{:08x}}};", key, i, def.node[i].ports[P1].data, def.node[i].ports[P2].data);
+      println!("  book->defs[0x{:08x}]->node[{:2}] = (Node) {{0x{:08x},0x{:08x}}};", key, i, cuda_ptr(def, def.node[i].ports[P1]), cuda_ptr(def, def.node[i].ports[P2]));
     }
   }
 }
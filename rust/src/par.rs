@@ -0,0 +1,764 @@
+// A lock-free parallel evaluator
+// ==============================
+//
+// This file implements the lock-free reduction algorithm described on the paper, on N OS threads.
+// When a worker takes a redex, it assumes ownership of its two active nodes. On annihilations, it
+// swaps their aux ports, turning variables into redirections, so the rewrite is semantically done
+// without touching the surrounding region. Then, for every port that held a main port, it calls
+// 'link', which walks the redirections, clearing them, until it reaches a port outside its region:
+// - if it is an aux port, the main port is moved there with an atomic compare-exchange;
+// - if it is a main port, the opposing worker is met, and one of both creates the new redex.
+// Ports are read and written atomically. While a worker moves a port, it stores a TKN placeholder
+// on it, so that other workers wait instead of reading a half-written state. Redirections that
+// connect two aux ports are never walked by 'link', so they're collected once all workers stop.
+// Redexes are handed to long-lived workers by the work-stealing scheduler of 'sched.rs'
+// ('reduce_sched').
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// An atomic pointer, of the same width as 'Val'.
+#[cfg(not(feature = "ptr64"))]
+type AtomicVal = std::sync::atomic::AtomicU32;
+#[cfg(feature = "ptr64")]
+type AtomicVal = std::sync::atomic::AtomicU64;
+
+use crate::core::*;
+use crate::sched::*;
+
+// Placeholders stored on ports while a rewrite is in flight. They have the NIL tag and a non-zero
+// value, so they never collide with a real pointer, nor with an empty port.
+const NEO: Ptr = Ptr::new(NIL, 1); // recently allocated node
+const TMP: Ptr = Ptr::new(NIL, 2); // node has been moved to a redex bag
+const TKN: Ptr = Ptr::new(NIL, 3); // port taken by another thread, will be replaced soon
+
+// Number of rewrites a scheduled worker claims from the budget at once.
+const RWTS_CHUNK: usize = 256;
+
+// Minimum length of a worker's 'dead' before it drops the nodes that are empty again.
+const MIN_RDRS: usize = 1 << 16;
+
+// An atomic view of a net's memory: the root, and the aux ports and label of every node.
+struct Heap<'a> {
+  root: &'a AtomicVal,
+  node: &'a [AtomicVal],
+}
+
+// A worker has:
+// - heap: the shared net memory.
+// - book: the shared definitions.
+// - aloc: where to alloc next node.
+// - acts: the local redex bag.
+// - dead: nodes freed by this worker, whose ports may still hold redirections or a TMP.
+// - locs: local alloc locs, used by deref.
+// - spare: nodes reserved for the next interactions, so that one never starts without its nodes.
+// - rwts: local rewrites performed.
+// - stats: local rewrites performed, by rule.
+// - used: local variation of allocated nodes.
+// - left: nodes this worker may still allocate. Budgets are split so that together they never
+//   exceed the empty nodes of the heap, which then can't fill up while workers run.
+// - hungry: set when the budget wasn't enough for the next interaction.
+// - fail: set when the next interaction expands a REF that can't be expanded, or when its nodes
+//   couldn't be reserved.
+struct Worker<'a> {
+  heap: &'a Heap<'a>,
+  book: &'a Book,
+  aloc: usize,
+  acts: Vec<(Ptr, Ptr)>,
+  dead: Vec<Val>,
+  locs: Vec<Val>,
+  spare: Vec<Val>,
+  rwts: usize,
+  stats: Stats,
+  used: isize,
+  left: usize,
+  hungry: bool,
+  fail: Option<RuntimeError>,
+}
+
+impl<'a> Heap<'a> {
+  // Gets the port P1 or P2 of the node at given index.
+  #[inline(always)]
+  fn at(&self, index: Val, port: Port) -> &AtomicVal {
+    unsafe {
+      return self.node.get_unchecked(index as usize * 3 + port);
+    }
+  }
+
+  // Gets the label of the node at given index. It is only written when the node is allocated, by
+  // the worker that owns it, before any pointer to it is published.
+  #[inline(always)]
+  fn lab(&self, index: Val) -> &AtomicVal {
+    unsafe {
+      return self.node.get_unchecked(index as usize * 3 + 2);
+    }
+  }
+
+  // Gets the port a variable or redirection points to.
+  #[inline(always)]
+  fn target(&self, ptr: Ptr) -> &AtomicVal {
+    match ptr.tag() {
+      VRR | RDR => self.root,
+      VR1 | RD1 => self.at(ptr.loc(), P1),
+      VR2 | RD2 => self.at(ptr.loc(), P2),
+      _         => unreachable!(),
+    }
+  }
+
+  #[inline(always)]
+  fn load(&self, port: &AtomicVal) -> Ptr {
+    Ptr { data: port.load(Ordering::Acquire) }
+  }
+
+  #[inline(always)]
+  fn store(&self, port: &AtomicVal, value: Ptr) {
+    port.store(value.data, Ordering::Release);
+  }
+
+  #[inline(always)]
+  fn swap(&self, port: &AtomicVal, value: Ptr) -> Ptr {
+    Ptr { data: port.swap(value.data, Ordering::AcqRel) }
+  }
+
+  #[inline(always)]
+  fn replace(&self, port: &AtomicVal, exp: Ptr, neo: Ptr) -> bool {
+    port.compare_exchange(exp.data, neo.data, Ordering::AcqRel, Ordering::Acquire).is_ok()
+  }
+
+  // Follows a variable or redirection until it reaches a port that isn't a redirection. Returns
+  // the pointer to that port, or None if the redirections form a loop longer than 'limit'.
+  fn enter(&self, ptr: Ptr, limit: usize) -> Option<Ptr> {
+    let mut ptr = ptr;
+    for _ in 0 .. limit {
+      let got = self.load(self.target(ptr));
+      if !got.is_red() {
+        return Some(ptr);
+      }
+      ptr = got;
+    }
+    return None;
+  }
+}
+
+impl<'a> Worker<'a> {
+  fn new(heap: &'a Heap<'a>, book: &'a Book, aloc: usize, left: usize) -> Self {
+    Worker {
+      heap,
+      book,
+      aloc,
+      acts: vec![],
+      dead: vec![],
+      locs: vec![0; book.largest()],
+      spare: vec![],
+      rwts: 0,
+      stats: Stats::default(),
+      used: 0,
+      left,
+      hungry: false,
+      fail: None,
+    }
+  }
+
+  // Forgets freed nodes that are done with, giving them back to the budget. A node is done with
+  // when neither port holds a redirection or a TMP: it is either empty, or was allocated again,
+  // which means it was empty at some point. If 'force' is not set, only prunes long lists.
+  fn prune(&mut self, force: bool) {
+    if force || self.dead.len() >= MIN_RDRS {
+      let heap = self.heap;
+      let busy = |ptr: Ptr| ptr.is_red() || ptr == TMP;
+      let len = self.dead.len();
+      self.dead.retain(|&index| busy(heap.load(heap.at(index, P1))) || busy(heap.load(heap.at(index, P2))));
+      self.left += len - self.dead.len();
+      self.dead.reserve(self.dead.len());
+    }
+  }
+
+  // Reserves empty nodes with NEO placeholders until there are 'size' spare ones. Fails if a scan
+  // of the whole heap, twice over, finds no empty node, leaving the nodes it did reserve as spare.
+  fn reserve(&mut self, size: usize) -> Result<(), RuntimeError> {
+    let nodes = self.heap.node.len() / 3;
+    let mut tries = 0;
+    while self.spare.len() < size {
+      let index = (self.aloc % nodes) as Val;
+      self.aloc = index as usize + 1;
+      let p1 = self.heap.at(index, P1);
+      if self.heap.replace(p1, Ptr::new(NIL, 0), NEO) {
+        if self.heap.replace(self.heap.at(index, P2), Ptr::new(NIL, 0), NEO) {
+          self.left -= 1;
+          self.spare.push(index);
+          tries = 0;
+          continue;
+        }
+        self.heap.store(p1, Ptr::new(NIL, 0));
+      }
+      tries += 1;
+      if tries > nodes * 2 {
+        return Err(RuntimeError::OutOfMemory { used: nodes, limit: nodes });
+      }
+    }
+    return Ok(());
+  }
+
+  // Empties the spare nodes, once the worker is done.
+  fn release(&mut self) {
+    for index in self.spare.drain(..) {
+      self.heap.store(self.heap.at(index, P1), Ptr::new(NIL, 0));
+      self.heap.store(self.heap.at(index, P2), Ptr::new(NIL, 0));
+      self.left += 1;
+    }
+  }
+
+  // Allocates a new node with given label, taking one of the spare nodes reserved for the
+  // interaction.
+  #[inline(always)]
+  fn alloc(&mut self, lab: Lab) -> Val {
+    let index = self.spare.pop().expect("interaction allocated more nodes than it demanded");
+    self.heap.lab(index).store(lab, Ordering::Relaxed);
+    self.used += 1;
+    return index;
+  }
+
+  // Gets the label of the node a pointer points to.
+  #[inline(always)]
+  fn lab(&self, ptr: Ptr) -> Lab {
+    return self.heap.lab(ptr.loc()).load(Ordering::Relaxed);
+  }
+
+  // Takes the value of an owned port, leaving a TKN placeholder.
+  #[inline(always)]
+  fn take(&self, port: &AtomicVal) -> Ptr {
+    let got = self.heap.swap(port, TKN);
+    debug_assert!(got != TKN);
+    return got;
+  }
+
+  // Atomically links the main port in 'src' towards 'dir'.
+  fn link(&mut self, src: &AtomicVal, dir: Ptr) {
+    let heap = self.heap;
+    let mut dir = dir;
+    loop {
+      // Peeks the target, which may not be owned by us.
+      let trg = heap.target(dir);
+      let ptr = heap.load(trg);
+
+      // If target is a redirection, we own it, so we clear it and move forward.
+      if ptr.is_red() {
+        heap.store(trg, Ptr::new(NIL, 0));
+        dir = ptr;
+
+      // If target is an aux port, we try replacing it by the main port.
+      } else if ptr.is_var() {
+        if heap.replace(trg, ptr, heap.load(src)) {
+          // Collects the orphaned backward path.
+          let mut trg = heap.target(ptr);
+          let mut ptr = heap.load(trg);
+          while ptr.is_red() {
+            heap.store(trg, Ptr::new(NIL, 0));
+            trg = heap.target(ptr);
+            ptr = heap.load(trg);
+          }
+          // Clears the source port.
+          heap.store(src, Ptr::new(NIL, 0));
+          return;
+        }
+
+      // If target is a main port, two workers will reach this branch.
+      } else if ptr.is_pri() || ptr == TMP {
+        // Sorts the ports, to avoid deadlocks.
+        let (fst, snd) = if (src as *const _) < (trg as *const _) { (src, trg) } else { (trg, src) };
+        // The first to arrive creates the redex.
+        let fst_ptr = heap.swap(fst, TMP);
+        if fst_ptr != TMP {
+          let snd_ptr = heap.swap(snd, TMP);
+          self.acts.push((fst_ptr, snd_ptr));
+        // The second to arrive clears the memory.
+        } else {
+          heap.store(fst, Ptr::new(NIL, 0));
+          while !heap.replace(snd, TMP, Ptr::new(NIL, 0)) {
+            std::hint::spin_loop();
+          }
+        }
+        return;
+
+      // If it is taken, we wait.
+      } else {
+        std::hint::spin_loop();
+      }
+    }
+  }
+
+  // Stores 'value' on an owned port, then moves it to whatever that port was connected to.
+  #[inline(always)]
+  fn send(&mut self, index: Val, port: Port, value: Ptr) {
+    let heap = self.heap;
+    let here = heap.at(index, port);
+    let got = self.take(here);
+    heap.store(here, value);
+    if got.is_var() {
+      self.link(here, got.redirect());
+    } else if got.is_pri() {
+      heap.store(here, Ptr::new(NIL, 0));
+      self.acts.push((got, value));
+    }
+  }
+
+  // Performs an interaction over a redex, if it fits on the budget, its REFs can be expanded and
+  // its nodes can be reserved. Returns whether it did.
+  fn try_interact(&mut self, a: Ptr, b: Ptr) -> bool {
+    let demand = match self.book.demand(a, b) {
+      Ok(demand) => demand,
+      Err(err)   => { self.fail = Some(err); return false; }
+    };
+    let need = demand.saturating_sub(self.spare.len());
+    if need > self.left {
+      self.prune(true);
+    }
+    if need > self.left {
+      self.hungry = true;
+      return false;
+    }
+    if let Err(err) = self.reserve(demand) {
+      self.fail = Some(err);
+      return false;
+    }
+    self.interact(a, b);
+    return true;
+  }
+
+  // Performs an interaction over a redex.
+  fn interact(&mut self, a: Ptr, b: Ptr) {
+    let mut a = a;
+    let mut b = b;
+    self.rwts += 1;
+    // Dereference
+    if a.tag() == REF && b.tag() != ERA {
+      a = self.deref(a, Ptr::new(NIL, 0));
+    }
+    if a.tag() != ERA && b.tag() == REF {
+      b = self.deref(b, Ptr::new(NIL, 0));
+    }
+    // VAR (only happens on deref roots, which we own)
+    if a.is_var() || b.is_var() {
+      self.stats.link += 1;
+      if a.is_var() {
+        self.heap.store(self.heap.target(a), b);
+      }
+      if b.is_var() {
+        self.heap.store(self.heap.target(b), a);
+      }
+    // CON-CON
+    } else if a.is_ctr() && b.is_ctr() && self.lab(a) == self.lab(b) {
+      self.stats.anni += 1;
+      self.annihilate(a, b);
+    // CON-DUP, and any pair with an operation or match node
+    } else if a.is_nod() && b.is_nod() {
+      self.stats.comm += 1;
+      self.commute(a, b);
+    // OP2-NUM
+    } else if a.tag() == OP2 && b.is_num() {
+      self.stats.oper += 1;
+      self.op2(a, b);
+    // NUM-OP2
+    } else if a.is_num() && b.tag() == OP2 {
+      self.stats.oper += 1;
+      self.op2(b, a);
+    // OP1-NUM
+    } else if a.tag() == OP1 && b.is_num() {
+      self.stats.oper += 1;
+      self.op1(a, b);
+    // NUM-OP1
+    } else if a.is_num() && b.tag() == OP1 {
+      self.stats.oper += 1;
+      self.op1(b, a);
+    // MAT-NUM
+    } else if a.is_mat() && b.is_num() {
+      self.stats.oper += 1;
+      self.mat(a, b);
+    // NUM-MAT
+    } else if a.is_num() && b.is_mat() {
+      self.stats.oper += 1;
+      self.mat(b, a);
+    // CTR-NUM
+    } else if a.is_ctr() && b.is_num() {
+      self.stats.copy += 1;
+      self.erase(a, Ptr::new(NUM, b.val()));
+    // NUM-CTR
+    } else if a.is_num() && b.is_ctr() {
+      self.stats.copy += 1;
+      self.erase(b, Ptr::new(NUM, a.val()));
+    // NOD-ERA
+    } else if a.is_nod() && b.is_era() {
+      self.stats.eras += 1;
+      self.erase(a, Ptr::new(ERA, 0));
+    // ERA-NOD
+    } else if a.is_era() && b.is_nod() {
+      self.stats.eras += 1;
+      self.erase(b, Ptr::new(ERA, 0));
+    // REF-ERA
+    } else if a.is_ref() || b.is_ref() {
+      self.stats.coll += 1;
+    // Otherwise, both sides are ERAs or NUMs, which just vanish.
+    } else {
+      self.stats.void += 1;
+    }
+  }
+
+  // Annihilates two nodes by swapping their aux ports as redirections, then links main ports.
+  fn annihilate(&mut self, a: Ptr, b: Ptr) {
+    let heap = self.heap;
+    let a1 = self.take(heap.at(a.loc(), P1));
+    let a2 = self.take(heap.at(a.loc(), P2));
+    let b1 = self.take(heap.at(b.loc(), P1));
+    let b2 = self.take(heap.at(b.loc(), P2));
+    heap.store(heap.at(a.loc(), P1), b1.redirect());
+    heap.store(heap.at(a.loc(), P2), b2.redirect());
+    heap.store(heap.at(b.loc(), P1), a1.redirect());
+    heap.store(heap.at(b.loc(), P2), a2.redirect());
+    self.dead.push(a.loc());
+    self.dead.push(b.loc());
+    self.used -= 2;
+    if a1.is_pri() {
+      self.link(heap.at(b.loc(), P1), Ptr::new(RD1, a.loc()));
+    }
+    if a2.is_pri() {
+      self.link(heap.at(b.loc(), P2), Ptr::new(RD2, a.loc()));
+    }
+    if b1.is_pri() {
+      self.link(heap.at(a.loc(), P1), Ptr::new(RD1, b.loc()));
+    }
+    if b2.is_pri() {
+      self.link(heap.at(a.loc(), P2), Ptr::new(RD2, b.loc()));
+    }
+  }
+
+  // Commutes two nodes, sending a clone of each one to every aux port of the other. An OP1 node has
+  // a single aux port, P2, as its P1 holds the left operand, which its clones keep.
+  fn commute(&mut self, a: Ptr, b: Ptr) {
+    let heap = self.heap;
+    let a_aux: &[Port] = if a.tag() == OP1 { &[P2] } else { &[P1, P2] };
+    let b_aux: &[Port] = if b.tag() == OP1 { &[P2] } else { &[P1, P2] };
+    let mut x = [0; 2];
+    let mut y = [0; 2];
+    let (a_lab, b_lab) = (self.lab(a), self.lab(b));
+    for x in &mut x[0 .. a_aux.len()] {
+      *x = self.alloc(b_lab);
+    }
+    for y in &mut y[0 .. b_aux.len()] {
+      *y = self.alloc(a_lab);
+    }
+    for (i, &a_port) in a_aux.iter().enumerate() {
+      for (j, &b_port) in b_aux.iter().enumerate() {
+        heap.store(heap.at(x[i], b_port), Ptr::new(if a_port == P1 { VR1 } else { VR2 }, y[j]));
+        heap.store(heap.at(y[j], a_port), Ptr::new(if b_port == P1 { VR1 } else { VR2 }, x[i]));
+      }
+    }
+    if a.tag() == OP1 {
+      let lft = self.take(heap.at(a.loc(), P1));
+      heap.store(heap.at(a.loc(), P1), Ptr::new(NIL, 0));
+      for &y in &y[0 .. b_aux.len()] {
+        heap.store(heap.at(y, P1), lft);
+      }
+    }
+    if b.tag() == OP1 {
+      let lft = self.take(heap.at(b.loc(), P1));
+      heap.store(heap.at(b.loc(), P1), Ptr::new(NIL, 0));
+      for &x in &x[0 .. a_aux.len()] {
+        heap.store(heap.at(x, P1), lft);
+      }
+    }
+    self.used -= 2;
+    self.dead.push(a.loc());
+    self.dead.push(b.loc());
+    for (i, &a_port) in a_aux.iter().enumerate() {
+      self.send(a.loc(), a_port, Ptr::new(b.tag(), x[i]));
+    }
+    for (j, &b_port) in b_aux.iter().enumerate() {
+      self.send(b.loc(), b_port, Ptr::new(a.tag(), y[j]));
+    }
+  }
+
+  // Erases a node, sending 'value' (an ERA or a NUM) to both of its aux ports.
+  fn erase(&mut self, a: Ptr, value: Ptr) {
+    self.used -= 1;
+    self.dead.push(a.loc());
+    self.send(a.loc(), P1, value);
+    self.send(a.loc(), P2, value);
+  }
+
+  // Moves the wire on an owned port of a dying node to an owned port of a new node. A variable is
+  // wired as an annihilation would: through redirections on dead ports, from both sides, so that
+  // either side can send a main port through. The dying node must be added to 'dead'.
+  fn move_port(&mut self, src: Val, src_port: Port, dst: Val, dst_port: Port) {
+    let heap = self.heap;
+    let got = self.take(heap.at(src, src_port));
+    if got.is_var() {
+      let tmp = self.alloc(0);
+      heap.store(heap.at(tmp, P1), got.redirect());
+      heap.store(heap.at(tmp, P2), Ptr::new(NIL, 0));
+      heap.store(heap.at(dst, dst_port), Ptr::new(VR1, tmp));
+      heap.store(heap.at(src, src_port), Ptr::new(if dst_port == P1 { RD1 } else { RD2 }, dst));
+      self.used -= 1;
+      self.dead.push(tmp);
+    } else {
+      heap.store(heap.at(dst, dst_port), got);
+      heap.store(heap.at(src, src_port), Ptr::new(NIL, 0));
+    }
+  }
+
+  // Gives the left operand 'b' to an OP2 node 'a': a new OP1 node, holding 'b' and the output, is
+  // sent to the right operand, even if it is already a number, as in 'core.rs'.
+  fn op2(&mut self, a: Ptr, b: Ptr) {
+    let heap = self.heap;
+    let op1 = self.alloc(0);
+    heap.store(heap.at(op1, P1), b);
+    self.move_port(a.loc(), P2, op1, P2);
+    self.used -= 1;
+    self.dead.push(a.loc());
+    self.send(a.loc(), P1, Ptr::new(OP1, op1));
+  }
+
+  // Gives the right operand 'b' to an OP1 node 'a', sending the result to its output.
+  fn op1(&mut self, a: Ptr, b: Ptr) {
+    let heap = self.heap;
+    let lft = self.take(heap.at(a.loc(), P1));
+    heap.store(heap.at(a.loc(), P1), Ptr::new(NIL, 0));
+    self.erase_op(a, lft.operate(b));
+  }
+
+  // Gives the number 'b' to a MAT node 'a', sending new branches to its P1: '(0 r *)' if it is
+  // zero, or '(0 * (0 pred r))' otherwise, where 'r' is the output of the node.
+  fn mat(&mut self, a: Ptr, b: Ptr) {
+    let heap = self.heap;
+    let arms = self.alloc(CON);
+    if b.num() == 0 {
+      heap.store(heap.at(arms, P2), Ptr::new(ERA, 0));
+      self.move_port(a.loc(), P2, arms, P1);
+    } else {
+      let succ = self.alloc(CON);
+      heap.store(heap.at(succ, P1), Ptr::new_num(USE, b.num() - 1));
+      self.move_port(a.loc(), P2, succ, P2);
+      heap.store(heap.at(arms, P1), Ptr::new(ERA, 0));
+      heap.store(heap.at(arms, P2), Ptr::new(CTR, succ));
+    }
+    self.used -= 1;
+    self.dead.push(a.loc());
+    self.send(a.loc(), P1, Ptr::new(CTR, arms));
+  }
+
+  // Frees an operation node whose P1 was cleared, sending 'value' to its output.
+  fn erase_op(&mut self, a: Ptr, value: Ptr) {
+    self.used -= 1;
+    self.dead.push(a.loc());
+    self.send(a.loc(), P2, value);
+  }
+
+  // Expands a REF into its definition (a closed net). Its nodes are owned by this worker.
+  fn deref(&mut self, ptr: Ptr, parent: Ptr) -> Ptr {
+    let heap = self.heap;
+    let mut ptr = ptr;
+    // White ptr is still a REF...
+    while ptr.is_ref() {
+      // Loads the referenced definition...
+      if let Some(got) = self.book.get(ptr.val()) {
+        self.stats.dref += 1;
+        // Allocates enough space...
+        if self.locs.len() < got.node.len() {
+          self.locs.resize(got.node.len(), 0);
+        }
+        for i in 0 .. got.node.len() {
+          let loc = self.alloc(got.node[i].lab);
+          unsafe {
+            *self.locs.get_unchecked_mut(i) = loc;
+          }
+        }
+        // Loads nodes, adjusting locations...
+        for i in 0 .. got.node.len() {
+          unsafe {
+            let got = *got.node.get_unchecked(i);
+            let loc = *self.locs.get_unchecked(i);
+            heap.store(heap.at(loc, P1), got.port(P1).adjust(&self.locs));
+            heap.store(heap.at(loc, P2), got.port(P2).adjust(&self.locs));
+          }
+        }
+        // Loads redexes, adjusting locations...
+        for got in &got.acts {
+          let p1 = got.0.adjust(&self.locs);
+          let p2 = got.1.adjust(&self.locs);
+          self.acts.push((p1, p2));
+        }
+        // Overwrites 'ptr' with the loaded root pointer, adjusting locations...
+        ptr = got.root.adjust(&self.locs);
+        // Links root
+        if ptr.is_var() {
+          heap.store(heap.target(ptr), parent);
+        }
+      }
+    }
+    return ptr;
+  }
+}
+
+// Relinks aux-to-aux wires that still go through redirections, then clears them. Must only be
+// called when no worker is running.
+fn resolve(heap: &Heap, workers: &[Worker]) {
+  let dead = workers.iter().map(|worker| worker.dead.len()).sum::<usize>();
+  for worker in workers {
+    for &index in &worker.dead {
+      for port in [P1, P2] {
+        if !heap.load(heap.at(index, port)).is_red() {
+          continue;
+        }
+        let dir = if port == P1 { Ptr::new(RD1, index) } else { Ptr::new(RD2, index) };
+        if let Some(fst) = heap.enter(dir, dead * 2 + 1) {
+          let var = heap.load(heap.target(fst));
+          if var.is_var() && heap.load(heap.target(var)).is_red() {
+            if let Some(snd) = heap.enter(var, dead * 2 + 1) {
+              heap.store(heap.target(fst), Ptr::new(snd.tag() - 3, snd.loc()));
+              heap.store(heap.target(snd), Ptr::new(fst.tag() - 3, fst.loc()));
+            }
+          }
+        }
+      }
+    }
+  }
+  for worker in workers {
+    for &index in &worker.dead {
+      for port in [P1, P2] {
+        if heap.load(heap.at(index, port)).is_red() {
+          heap.store(heap.at(index, port), Ptr::new(NIL, 0));
+        }
+      }
+    }
+  }
+}
+
+impl Net {
+  // Reduces redexes with one long-lived worker per bag of 'sched', until there is none or the
+  // budget is spent. Redexes that were not reduced are kept on the net.
+  pub fn reduce_sched(&mut self, book: &Book, sched: &Sched, meter: &Meter) -> Result<(), RuntimeError> {
+    self.make_room();
+    let size = self.node.len();
+    let threads = sched.workers();
+    let left = size.min(self.limit).saturating_sub(self.used) / threads;
+    let root = AtomicVal::new(self.root.data);
+    let claimed = AtomicUsize::new(self.rwts);
+    // Views nodes as a flat array of atomic values. This is sound because Node is a repr(C) pair
+    // of repr(transparent) Val pointers, followed by a Val label, and AtomicVal has the same layout
+    // as Val.
+    let node = unsafe {
+      std::slice::from_raw_parts(self.node.as_mut_ptr() as *const AtomicVal, size * 3)
+    };
+    let heap = Heap { root: &root, node };
+    sched.fill(std::mem::take(&mut self.acts));
+    let work = |tid: usize| {
+      let mut worker = Worker::new(&heap, book, tid * size / threads, left);
+      let mut next = None;
+      let mut quota = 0;
+      while let Some((a, b)) = next.take().or_else(|| sched.next(tid)) {
+        // Out of rewrites: claims more, so that together workers never go past the budget, or
+        // stops everyone once it is spent.
+        if quota == 0 {
+          let claim = claimed.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |done| {
+            (done < meter.max_step).then(|| done + RWTS_CHUNK.min(meter.max_step - done))
+          });
+          quota = claim.map_or(0, |done| RWTS_CHUNK.min(meter.max_step - done));
+          if quota == 0 || meter.late() {
+            worker.acts.push((a, b));
+            sched.stop();
+            break;
+          }
+          worker.prune(false);
+        }
+        // Out of nodes, or failed: stops everyone, so that the heap can grow, or the error be
+        // returned.
+        if !worker.try_interact(a, b) {
+          worker.acts.push((a, b));
+          sched.stop();
+          break;
+        }
+        quota -= 1;
+        // Keeps reducing the newest redex, and lets other workers steal the rest.
+        next = worker.acts.pop();
+        sched.push(tid, &mut worker.acts);
+        if sched.stopped() {
+          break;
+        }
+      }
+      worker.acts.extend(next);
+      sched.push(tid, &mut worker.acts);
+      worker.release();
+      worker
+    };
+    let mut workers = if threads == 1 {
+      vec![work(0)]
+    } else {
+      std::thread::scope(|s| {
+        let work = &work;
+        let tasks : Vec<_> = (0 .. threads).map(|tid| s.spawn(move || work(tid))).collect();
+        tasks.into_iter().map(|task| task.join().unwrap()).collect()
+      })
+    };
+    resolve(&heap, &workers);
+    // Gathers results.
+    self.root = heap.load(&root);
+    self.reclaim();
+    self.acts = sched.drain();
+    let rwts = workers.iter().map(|worker| worker.rwts).sum::<usize>();
+    let hungry = workers.iter().any(|worker| worker.hungry);
+    let fail = workers.iter().find_map(|worker| worker.fail);
+    for worker in &mut workers {
+      self.rwts += worker.rwts;
+      self.stats.merge(&worker.stats);
+      self.used = (self.used as isize + worker.used) as usize;
+    }
+    self.stats.peak = self.stats.peak.max(self.used);
+    self.stats.rnds += 1;
+    self.debug_validate();
+    if let Some(err) = fail {
+      return Err(err);
+    }
+    if hungry && rwts == 0 {
+      self.starve(book)?;
+    }
+    return Ok(());
+  }
+
+  // Reduces all redexes until there is none, using up to 'threads' workers, or until the budget is
+  // spent. Returns how it ended, with the statistics of the net. With the 'checked' feature, runs
+  // depth-first on a single thread instead, since workers don't check what they read.
+  pub fn normal_par(&mut self, book: &Book, budget: Budget, threads: usize) -> Result<(Outcome, Stats), RuntimeError> {
+    if cfg!(feature = "checked") {
+      return self.normal_dfs(book, budget);
+    }
+    let sched = Sched::new(threads);
+    return self.spend(budget, |net, meter| {
+      net.expand(book, Ptr::new(VRR, 0))?;
+      while !net.acts.is_empty() {
+        if let Some(outcome) = meter.spent(net) {
+          return Ok(outcome);
+        }
+        net.reduce_sched(book, &sched, meter)?;
+        if net.acts.is_empty() {
+          net.expand(book, Ptr::new(VRR, 0))?;
+        }
+      }
+      return Ok(Outcome::Normal);
+    });
+  }
+
+  // Grows the heap before a parallel run if it is over half full, since workers can't grow it.
+  fn make_room(&mut self) {
+    if self.used * 2 > self.node.len() {
+      self.grow(0);
+    }
+  }
+
+  // Handles a parallel run where no worker had the budget for its next interaction. Grows the
+  // heap if possible; otherwise, a sequential round uses the space that is left, failing if it
+  // isn't enough.
+  fn starve(&mut self, book: &Book) -> Result<usize, RuntimeError> {
+    if self.grow(0) {
+      return Ok(0);
+    }
+    return self.reduce(book);
+  }
+}
@@ -0,0 +1,71 @@
+// Partial evaluation of definitions
+// =================================
+//
+// This file implements a pass over a book that reduces the redexes of each definition ahead of
+// time, and stores the reduced net back. A definition like 'ex0' or 'lowO' ships with redexes
+// that would otherwise be reduced again on every expansion, always to the same result.
+//
+// Only REFs to definitions that aren't recursive are expanded, as a recursive one could expand
+// forever; redexes with a REF to one are kept as they are, to be reduced at runtime. Even so, a
+// net of combinators can reduce forever, or grow past any heap, so a definition that takes more
+// than MAX_RWTS rewrites, or MAX_NODE nodes, is left as it was.
+
+use crate::core::*;
+
+// Most rewrites that precomputing a definition may take.
+const MAX_RWTS: usize = 1 << 16;
+
+// Most nodes that precomputing a definition may use.
+const MAX_NODE: usize = 1 << 16;
+
+impl Book {
+  // Reduces the redexes of every definition, other than those with a REF to a recursive one, and
+  // stores the result back. Definitions are reduced after those they refer to, so each expands
+  // to nets that are already reduced. Returns how many definitions were changed.
+  pub fn precompute(&mut self) -> usize {
+    let comps = self.components();
+    let recursive = self.recursive(&comps);
+    let mut done = 0;
+    for ids in &comps {
+      for &id in ids {
+        if let Some(def) = self.reduced(id, &recursive) {
+          self.def(id, def);
+          done += 1;
+        }
+      }
+    }
+    return done;
+  }
+
+  // Expands a definition on a net of its own, and reduces it, other than the redexes with a REF
+  // to a recursive or missing definition. Returns nothing if there was nothing to reduce, or if
+  // the reduction failed, or went past the limits.
+  fn reduced(&self, id: Val, recursive: &[bool]) -> Option<Def> {
+    let held = |ptr: Ptr| ptr.is_ref() && (self.get(ptr.val()).is_none() || recursive[ptr.val() as usize]);
+    let mut net = Net::new(MAX_NODE);
+    let work = &mut Worker::new(self);
+    net.room_for(self.expansion(Ptr::new(REF, id)).ok()?).ok()?;
+    net.root = net.deref(self, work, Ptr::new(REF, id), Ptr::new(VRR, 0)).ok()?;
+    // Reduces the redexes that can be, moved to the start of 'acts', until none is left.
+    loop {
+      let (mut acts, kept) : (Vec<_>, Vec<_>) = std::mem::take(&mut net.acts).into_iter().partition(|&(a, b)| !held(a) && !held(b));
+      let size = acts.len();
+      acts.extend(kept);
+      net.acts = acts;
+      if size == 0 {
+        break;
+      }
+      if net.rwts > MAX_RWTS {
+        return None;
+      }
+      net.reduce_upto(self, work, &Budget::steps(net.rwts + size).start()).ok()?;
+      net.debug_validate();
+    }
+    if net.rwts == 0 {
+      return None;
+    }
+    net.compact(false);
+    net.node.truncate(net.used);
+    return Some(Def::from(net));
+  }
+}
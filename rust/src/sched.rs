@@ -0,0 +1,171 @@
+// A work-stealing redex scheduler
+// ===============================
+//
+// This file implements a scheduler that splits the redexes of a net between a set of workers. Each
+// worker has its own redex bag, which it uses as a stack: new redexes are pushed to its end, and
+// the most recent one is reduced first, giving a depth-first order that keeps memory usage low on
+// recursive programs. When a worker runs out of redexes, it steals the oldest half of another bag,
+// which tends to be the largest pending computations. Workers only wait when every bag is empty,
+// and reduction ends when all of them are idle at once. A scheduler with a single bag can be used
+// by the sequential reducer, which then runs depth-first instead of round by round.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::core::*;
+
+// A scheduler has:
+// - bags: one redex bag per worker.
+// - idle: number of workers that found no redex to reduce.
+// - stop: set when reduction must end early, even with pending redexes.
+pub struct Sched {
+  bags: Vec<Mutex<Vec<(Ptr, Ptr)>>>,
+  idle: AtomicUsize,
+  stop: AtomicBool,
+}
+
+impl Sched {
+  // Creates a scheduler with given number of workers.
+  pub fn new(workers: usize) -> Self {
+    Sched {
+      bags: (0 .. workers.max(1)).map(|_| Mutex::new(vec![])).collect(),
+      idle: AtomicUsize::new(0),
+      stop: AtomicBool::new(false),
+    }
+  }
+
+  // Number of workers (and bags) of this scheduler.
+  pub fn workers(&self) -> usize {
+    self.bags.len()
+  }
+
+  // Spreads redexes between all bags, and resets the scheduler for a new run.
+  pub fn fill(&self, acts: Vec<(Ptr, Ptr)>) {
+    for (i, redex) in acts.into_iter().enumerate() {
+      self.bags[i % self.bags.len()].lock().unwrap().push(redex);
+    }
+    self.idle.store(0, Ordering::SeqCst);
+    self.stop.store(false, Ordering::SeqCst);
+  }
+
+  // Takes all pending redexes, leaving every bag empty.
+  pub fn drain(&self) -> Vec<(Ptr, Ptr)> {
+    let mut acts = vec![];
+    for bag in &self.bags {
+      acts.append(&mut bag.lock().unwrap());
+    }
+    return acts;
+  }
+
+  // Moves redexes to the end of a worker's bag.
+  pub fn push(&self, tid: usize, acts: &mut Vec<(Ptr, Ptr)>) {
+    if !acts.is_empty() {
+      self.bags[tid].lock().unwrap().append(acts);
+    }
+  }
+
+  // Pops the most recent redex of a worker's bag.
+  pub fn pop(&self, tid: usize) -> Option<(Ptr, Ptr)> {
+    self.bags[tid].lock().unwrap().pop()
+  }
+
+  // Steals the oldest half of another worker's bag. Returns one of the stolen redexes and moves
+  // the others to this worker's bag.
+  pub fn steal(&self, tid: usize) -> Option<(Ptr, Ptr)> {
+    let size = self.bags.len();
+    for i in 1 .. size {
+      let victim = (tid + i) % size;
+      let mut stolen = if let Ok(mut bag) = self.bags[victim].try_lock() {
+        let half = bag.len().div_ceil(2);
+        bag.drain(0 .. half).collect::<Vec<_>>()
+      } else {
+        continue;
+      };
+      if let Some(redex) = stolen.pop() {
+        self.push(tid, &mut stolen);
+        return Some(redex);
+      }
+    }
+    return None;
+  }
+
+  // Gets the next redex a worker should reduce. If there is none, waits until some other worker
+  // produces one. Returns None when all workers are idle, or when reduction was stopped.
+  pub fn next(&self, tid: usize) -> Option<(Ptr, Ptr)> {
+    if let Some(redex) = self.pop(tid) {
+      return Some(redex);
+    }
+    self.idle.fetch_add(1, Ordering::SeqCst);
+    loop {
+      if self.stop.load(Ordering::Relaxed) || self.idle.load(Ordering::SeqCst) == self.bags.len() {
+        return None;
+      }
+      // Leaves the idle state before stealing, so that nobody sees all workers idle while this
+      // one is holding a stolen redex.
+      self.idle.fetch_sub(1, Ordering::SeqCst);
+      if let Some(redex) = self.steal(tid) {
+        return Some(redex);
+      }
+      self.idle.fetch_add(1, Ordering::SeqCst);
+      std::thread::yield_now();
+    }
+  }
+
+  // Asks all workers to stop, leaving pending redexes on their bags.
+  pub fn stop(&self) {
+    self.stop.store(true, Ordering::SeqCst);
+  }
+
+  // Checks if reduction was stopped.
+  pub fn stopped(&self) -> bool {
+    self.stop.load(Ordering::Relaxed)
+  }
+}
+
+impl Net {
+  // Reduces redexes depth-first, taking them from a worker's bag, until there is none or the
+  // budget is spent. Redexes that were not reduced are kept on the bag.
+  pub fn reduce_with(&mut self, book: &Book, sched: &Sched, tid: usize, meter: &Meter) -> Result<(), RuntimeError> {
+    let work = &mut Worker::new(book);
+    self.stats.rnds += 1;
+    sched.push(tid, &mut self.acts);
+    while self.rwts < meter.max_step && !(self.rwts.is_multiple_of(TICK) && meter.late()) {
+      let Some((mut a, mut b)) = self.acts.pop().or_else(|| sched.pop(tid)).or_else(|| sched.steal(tid)) else {
+        break;
+      };
+      if let Err(err) = book.demand(a, b).and_then(|size| self.room_for(size)) {
+        self.acts.push((a, b));
+        sched.push(tid, &mut self.acts);
+        return Err(err);
+      }
+      if let Err(err) = self.interact(book, work, &mut a, &mut b) {
+        sched.push(tid, &mut self.acts);
+        return Err(err);
+      }
+    }
+    sched.push(tid, &mut self.acts);
+    return Ok(());
+  }
+
+  // Reduces all redexes until there is none, depth-first, or until the budget is spent. Returns
+  // how it ended, with the statistics of the net.
+  pub fn normal_dfs(&mut self, book: &Book, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    let sched = Sched::new(1);
+    return self.spend(budget, |net, meter| {
+      net.expand(book, Ptr::new(VRR, 0))?;
+      while !net.acts.is_empty() {
+        if let Some(outcome) = meter.spent(net) {
+          return Ok(outcome);
+        }
+        let done = net.reduce_with(book, &sched, 0, meter);
+        net.acts = sched.drain();
+        net.debug_validate();
+        done?;
+        if net.acts.is_empty() {
+          net.expand(book, Ptr::new(VRR, 0))?;
+        }
+      }
+      return Ok(Outcome::Normal);
+    });
+  }
+}
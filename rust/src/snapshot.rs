@@ -0,0 +1,409 @@
+// Binary snapshots of nets and books
+// ==================================
+//
+// This file implements saving a net, possibly halfway through a reduction, to a compact binary
+// file, and loading it back to continue reducing. Pointers are stored in the layout of the CUDA
+// runtime ('cuda/hvm2.cu'), so that a snapshot can be inspected by the same tools as a GPU heap
+// dump: each pointer is a u32 with a 4-bit tag and a 28-bit value, nodes are pairs of pointers,
+// and redexes are 'Wire's, a u64 with the left pointer on the high half. There, constructors are
+// labelled by the tag of the pointers to them, rather than by their node, so only labels up to 2
+// (CON, DUP and TRI) fit, and numeric nodes and numbers with an operator don't fit at all. A net
+// with any of those is saved in the native layout instead, which only a build with the same
+// pointer width can load: pointers are stored as they are, and nodes with their label.
+//
+// A snapshot is, with every number in little-endian:
+// - magic: the bytes "HVMN".
+// - version: a u32, 1 for the CUDA layout, or 2 for the native layout.
+// - width: only on the native layout, a u32, the bits of a pointer.
+// - root: the root pointer.
+// - rwts: a u64, followed by a u64 for each field of 'Stats', in order.
+// - limit: a u64, the most nodes the heap can grow to.
+// - alen: a u64, followed by that many redexes. On the native layout, each is a pair of pointers.
+// - nlen: a u64, followed by that many nodes. Empty nodes at the end of the heap are left out. On
+//   the native layout, each node has its label after its pointers.
+//
+// It also implements compiled books ('.hvmb' files), which store every definition as the arrays
+// that 'define' would build, so that loading one skips parsing. Their pointers are stored as they
+// are, so numeric nodes and any label are kept, but a book can only be loaded by a build with the
+// same pointer width. A book is, with every number in little-endian:
+// - magic: the bytes "HVMB".
+// - version: a u32, currently 3.
+// - width: a u32, the bits of a pointer.
+// - slen: a u64, followed by that many symbols, sorted by id. Each symbol has:
+//   - id: a pointer-wide value.
+//   - name: a u32 length, followed by the name in UTF-8.
+// - dlen: a u64, followed by that many definitions, sorted by id. Each definition has:
+//   - id: a pointer-wide value.
+//   - root: the root pointer.
+//   - alen: a u64, followed by that many redexes, as pairs of pointers.
+//   - nlen: a u64, followed by that many nodes, each as its two pointers and its label.
+
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::core::*;
+use crate::validate::Invalid;
+
+const MAGIC: &[u8; 4] = b"HVMN";
+const VERSION: u32 = 1;
+const NATIVE_VERSION: u32 = 2;
+const BOOK_MAGIC: &[u8; 4] = b"HVMB";
+const BOOK_VERSION: u32 = 3;
+
+// A reason a net or a book couldn't be saved or loaded.
+#[derive(Debug)]
+pub enum SnapshotError {
+  // The file couldn't be read or written, or ended early.
+  Io(std::io::Error),
+  // The file doesn't start with the magic of what was being loaded.
+  BadMagic,
+  // The file is from a version of the format this build can't read.
+  BadVersion { found: u32 },
+  // A pointer of the file has no equivalent in this build.
+  Undecodable { data: u32 },
+  // The heap of the file is larger than its limit, or than pointers of this build can address.
+  TooLarge { nodes: usize, limit: usize },
+  // The book or snapshot was written by a build with pointers of another width.
+  BadWidth { found: u32 },
+  // A name of the symbol table isn't valid UTF-8.
+  BadName,
+  // A name of the symbol table can't be bound to its id.
+  BadSymbol(SymbolError),
+  // An id of a symbol or definition that a REF can't hold, or that is at least the count of them
+  // in the book, which a table of dense ids never gives.
+  BadId { id: Val },
+  // A definition that points outside of its nodes, or has a tag where it can't be.
+  BadDef { id: Val, err: RuntimeError },
+  // A pointer of a snapshot with a tag that can't be on a heap, or that points past its nodes.
+  BadPointer { ptr: Ptr },
+  // A snapshot whose net isn't well-formed, so reducing it would read the heap where it can't.
+  Invalid(Invalid),
+}
+
+impl std::fmt::Display for SnapshotError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      SnapshotError::Io(err) => {
+        write!(f, "snapshot i/o failed: {}", err)
+      }
+      SnapshotError::BadMagic => {
+        write!(f, "file isn't a snapshot of that kind")
+      }
+      SnapshotError::BadVersion { found } => {
+        write!(f, "snapshot version {} isn't supported", found)
+      }
+      SnapshotError::Undecodable { data } => {
+        write!(f, "pointer {:08x} doesn't fit this build's layout", data)
+      }
+      SnapshotError::TooLarge { nodes, limit } => {
+        write!(f, "snapshot has {} nodes, but the heap is limited to {}", nodes, limit)
+      }
+      SnapshotError::BadWidth { found } => {
+        write!(f, "file has {}-bit pointers, but this build uses {}-bit ones", found, Val::BITS)
+      }
+      SnapshotError::BadName => {
+        write!(f, "book has a name that isn't valid UTF-8")
+      }
+      SnapshotError::BadSymbol(err) => {
+        write!(f, "book has a bad symbol: {}", err)
+      }
+      SnapshotError::BadId { id } => {
+        write!(f, "book has an id out of range: #{}", id)
+      }
+      SnapshotError::BadDef { id, err } => {
+        write!(f, "book has a malformed definition #{}: {}", id, err)
+      }
+      SnapshotError::BadPointer { ptr } => {
+        write!(f, "snapshot has a bad pointer: {:?}", ptr)
+      }
+      SnapshotError::Invalid(err) => {
+        write!(f, "snapshot has an invalid net: {}", err)
+      }
+    }
+  }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+  fn from(err: std::io::Error) -> Self {
+    SnapshotError::Io(err)
+  }
+}
+
+// Converts a pointer to the CUDA runtime's layout, which always has a 4-bit tag and a 28-bit value.
+// There, constructors are labelled by their tag: CON, DUP and TRI are 0xA, 0xB and 0xC. So 'lab'
+// is the label of the node a constructor points to, and is ignored on other pointers. Numbers have
+// no operator there, so fails on those that carry one, rather than giving it as part of the value.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_cuda(ptr: Ptr, lab: Lab) -> Option<u32> {
+  let (tag, val) = match ptr.tag() {
+    CTR if lab <= 2         => (0xA + lab as u32, ptr.loc()),
+    NUM if ptr.opr() != USE => { return None; }
+    tag if tag < CTR        => (tag as u32, ptr.val()),
+    _                       => { return None; }
+  };
+  if val > 0xFFF_FFFF {
+    return None;
+  }
+  return Some((tag << 28) | val as u32);
+}
+
+// Converts a pointer from the CUDA runtime's layout, with the label of the node a constructor
+// points to, which is 0 on other pointers. Fails on tags that have no equivalent, and on numbers
+// too big for this build, which would otherwise be read as carrying an operator.
+pub fn from_cuda(data: u32) -> Option<(Ptr, Lab)> {
+  let (tag, val) = ((data >> 28) as Tag, (data & 0xFFF_FFFF) as Val);
+  return match tag {
+    0xA ..= 0xC           => Some((Ptr::new(CTR, val), (tag - 0xA) as Lab)),
+    NUM if val > NUM_MASK => None,
+    tag if tag < CTR      => Some((Ptr::new(tag, val), 0)),
+    _                     => None,
+  };
+}
+
+impl Net {
+  // Writes a snapshot of the net: its root, redexes, heap and counters. Profiles aren't saved. Uses
+  // the CUDA layout if every pointer fits it, or the native layout otherwise.
+  pub fn save(&self, out: &mut impl Write) -> Result<(), SnapshotError> {
+    let mut out = BufWriter::new(out);
+    let Stats { anni, comm, eras, copy, oper, coll, link, void, dref, peak, rnds } = self.stats;
+    let nlen = self.node.iter().rposition(|node| *node != Node::nil()).map_or(0, |index| index + 1);
+    let lab = |ptr: Ptr| if ptr.is_ctr() { self.node.get(ptr.loc() as usize).map_or(0, |node| node.lab) } else { 0 };
+    let acts = self.acts.iter().flat_map(|&(a, b)| [a, b]);
+    let ports = self.node[0 .. nlen].iter().flat_map(|node| node.ports);
+    let cuda = [self.root].into_iter().chain(acts).chain(ports).all(|ptr| to_cuda(ptr, lab(ptr)).is_some());
+    let put = |out: &mut BufWriter<_>, ptr: Ptr| -> Result<(), SnapshotError> {
+      match cuda {
+        true  => out.write_all(&to_cuda(ptr, lab(ptr)).unwrap().to_le_bytes())?,
+        false => out.write_all(&ptr.data.to_le_bytes())?,
+      }
+      return Ok(());
+    };
+    out.write_all(MAGIC)?;
+    if cuda {
+      out.write_all(&VERSION.to_le_bytes())?;
+    } else {
+      out.write_all(&NATIVE_VERSION.to_le_bytes())?;
+      out.write_all(&Val::BITS.to_le_bytes())?;
+    }
+    put(&mut out, self.root)?;
+    for count in [self.rwts, anni, comm, eras, copy, oper, coll, link, void, dref, peak, rnds, self.limit] {
+      out.write_all(&(count as u64).to_le_bytes())?;
+    }
+    out.write_all(&(self.acts.len() as u64).to_le_bytes())?;
+    for &(a, b) in &self.acts {
+      // A little-endian Wire has its right pointer first.
+      let (fst, snd) = if cuda { (b, a) } else { (a, b) };
+      put(&mut out, fst)?;
+      put(&mut out, snd)?;
+    }
+    out.write_all(&(nlen as u64).to_le_bytes())?;
+    for node in &self.node[0 .. nlen] {
+      put(&mut out, *node.port(P1))?;
+      put(&mut out, *node.port(P2))?;
+      if !cuda {
+        out.write_all(&node.lab.to_le_bytes())?;
+      }
+    }
+    out.flush()?;
+    return Ok(());
+  }
+
+  // Reads a snapshot written by 'save', giving a net that can be reduced further. As the reducers
+  // read the heap unchecked, every pointer is checked to have a tag and a location that fit, and
+  // the net is validated, before it is returned.
+  pub fn load(inp: &mut impl Read) -> Result<Net, SnapshotError> {
+    let mut inp = BufReader::new(inp);
+    let mut magic = [0; 4];
+    inp.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(SnapshotError::BadMagic);
+    }
+    let cuda = match read_u32(&mut inp)? {
+      VERSION        => true,
+      NATIVE_VERSION => false,
+      found          => { return Err(SnapshotError::BadVersion { found }); }
+    };
+    if !cuda {
+      let width = read_u32(&mut inp)?;
+      if width != Val::BITS {
+        return Err(SnapshotError::BadWidth { found: width });
+      }
+    }
+    // On the CUDA layout, labels are gathered from the pointers to constructors, and set on their
+    // nodes once read.
+    let mut labs = vec![];
+    let mut get = |inp: &mut BufReader<_>| -> Result<Ptr, SnapshotError> {
+      if !cuda {
+        return Ok(Ptr { data: read_val(inp)? });
+      }
+      let data = read_u32(inp)?;
+      let (ptr, lab) = from_cuda(data).ok_or(SnapshotError::Undecodable { data })?;
+      if lab != 0 {
+        labs.push((data, ptr.loc(), lab));
+      }
+      return Ok(ptr);
+    };
+    let root = get(&mut inp)?;
+    let mut counts = [0; 13];
+    for count in &mut counts {
+      *count = read_u64(&mut inp)? as usize;
+    }
+    let [rwts, anni, comm, eras, copy, oper, coll, link, void, dref, peak, rnds, limit] = counts;
+    let mut net = Net::new(limit);
+    net.root = root;
+    net.rwts = rwts;
+    net.stats = Stats { anni, comm, eras, copy, oper, coll, link, void, dref, peak, rnds };
+    let alen = read_u64(&mut inp)? as usize;
+    for _ in 0 .. alen {
+      let (fst, snd) = (get(&mut inp)?, get(&mut inp)?);
+      net.acts.push(if cuda { (snd, fst) } else { (fst, snd) });
+    }
+    let nlen = read_u64(&mut inp)? as usize;
+    if nlen > net.limit {
+      return Err(SnapshotError::TooLarge { nodes: nlen, limit: net.limit });
+    }
+    net.node.clear();
+    for _ in 0 .. nlen {
+      let (p1, p2) = (get(&mut inp)?, get(&mut inp)?);
+      let lab = if cuda { 0 } else { read_val(&mut inp)? };
+      let node = Node::new(p1, p2, lab);
+      net.used += (node != Node::nil()) as usize;
+      net.node.push(node);
+    }
+    for (data, loc, lab) in labs {
+      match net.node.get_mut(loc as usize) {
+        Some(node) => { node.lab = lab; }
+        None       => { return Err(SnapshotError::Undecodable { data }); }
+      }
+    }
+    let acts = net.acts.iter().flat_map(|&(a, b)| [a, b]);
+    let ports = net.node.iter().flat_map(|node| node.ports);
+    for ptr in [net.root].into_iter().chain(acts).chain(ports) {
+      if ptr.tag() > MAT || ptr.is_red() || ptr.has_loc() && ptr.loc() as usize >= nlen {
+        return Err(SnapshotError::BadPointer { ptr });
+      }
+    }
+    net.grow(nlen);
+    net.reclaim();
+    net.validate().map_err(SnapshotError::Invalid)?;
+    return Ok(net);
+  }
+}
+
+impl Book {
+  // Writes the book in the compiled format, with its definitions sorted by id.
+  pub fn save(&self, out: &mut impl Write) -> Result<(), SnapshotError> {
+    let mut out = BufWriter::new(out);
+    let defs: Vec<(Val, &Def)> = self.iter().collect();
+    out.write_all(BOOK_MAGIC)?;
+    out.write_all(&BOOK_VERSION.to_le_bytes())?;
+    out.write_all(&Val::BITS.to_le_bytes())?;
+    out.write_all(&(self.syms.iter().count() as u64).to_le_bytes())?;
+    for (id, name) in self.syms.iter() {
+      out.write_all(&id.to_le_bytes())?;
+      out.write_all(&(name.len() as u32).to_le_bytes())?;
+      out.write_all(name.as_bytes())?;
+    }
+    out.write_all(&(defs.len() as u64).to_le_bytes())?;
+    for (id, def) in defs {
+      out.write_all(&id.to_le_bytes())?;
+      out.write_all(&def.root.data.to_le_bytes())?;
+      out.write_all(&(def.acts.len() as u64).to_le_bytes())?;
+      for (a, b) in &def.acts {
+        out.write_all(&a.data.to_le_bytes())?;
+        out.write_all(&b.data.to_le_bytes())?;
+      }
+      out.write_all(&(def.node.len() as u64).to_le_bytes())?;
+      for node in &def.node {
+        out.write_all(&node.port(P1).data.to_le_bytes())?;
+        out.write_all(&node.port(P2).data.to_le_bytes())?;
+        out.write_all(&node.lab.to_le_bytes())?;
+      }
+    }
+    out.flush()?;
+    return Ok(());
+  }
+
+  // Reads a book written by 'save'. Nothing in the file is trusted: names are read as far as the
+  // file goes, and ids and definitions are checked before they are added to the book.
+  pub fn load(inp: &mut impl Read) -> Result<Book, SnapshotError> {
+    let mut inp = BufReader::new(inp);
+    let mut magic = [0; 4];
+    inp.read_exact(&mut magic)?;
+    if &magic != BOOK_MAGIC {
+      return Err(SnapshotError::BadMagic);
+    }
+    let version = read_u32(&mut inp)?;
+    if version != BOOK_VERSION {
+      return Err(SnapshotError::BadVersion { found: version });
+    }
+    let width = read_u32(&mut inp)?;
+    if width != Val::BITS {
+      return Err(SnapshotError::BadWidth { found: width });
+    }
+    let slen = read_u64(&mut inp)? as usize;
+    let mut syms = vec![];
+    for _ in 0 .. slen {
+      let id = read_val(&mut inp)?;
+      let len = read_u32(&mut inp)? as u64;
+      let mut name = vec![];
+      if inp.by_ref().take(len).read_to_end(&mut name)? as u64 != len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+      }
+      syms.push((id, String::from_utf8(name).map_err(|_| SnapshotError::BadName)?));
+    }
+    let dlen = read_u64(&mut inp)? as usize;
+    let mut defs = vec![];
+    for _ in 0 .. dlen {
+      let id = read_val(&mut inp)?;
+      let root = Ptr { data: read_val(&mut inp)? };
+      let alen = read_u64(&mut inp)? as usize;
+      let mut acts = vec![];
+      for _ in 0 .. alen {
+        acts.push((Ptr { data: read_val(&mut inp)? }, Ptr { data: read_val(&mut inp)? }));
+      }
+      let nlen = read_u64(&mut inp)? as usize;
+      if nlen > VAL_MASK as usize + 1 {
+        return Err(SnapshotError::TooLarge { nodes: nlen, limit: VAL_MASK as usize + 1 });
+      }
+      let mut node = vec![];
+      for _ in 0 .. nlen {
+        node.push(Node::new(Ptr { data: read_val(&mut inp)? }, Ptr { data: read_val(&mut inp)? }, read_val(&mut inp)?));
+      }
+      defs.push((id, Def { root, acts, node }));
+    }
+    // Ids are bounded by what was read, so that the tables they index stay as large as the file.
+    let count = syms.len() + defs.len();
+    let check_id = |id: Val| if id > VAL_MASK || id as usize >= count { Err(SnapshotError::BadId { id }) } else { Ok(()) };
+    let mut book = Book::new();
+    for (id, name) in syms {
+      check_id(id)?;
+      book.syms.bind(&name, id).map_err(SnapshotError::BadSymbol)?;
+    }
+    for (id, def) in defs {
+      check_id(id)?;
+      def.check_def().map_err(|err| SnapshotError::BadDef { id, err })?;
+      book.def(id, def);
+    }
+    return Ok(book);
+  }
+}
+
+fn read_u32(inp: &mut impl Read) -> Result<u32, SnapshotError> {
+  let mut bytes = [0; 4];
+  inp.read_exact(&mut bytes)?;
+  return Ok(u32::from_le_bytes(bytes));
+}
+
+fn read_u64(inp: &mut impl Read) -> Result<u64, SnapshotError> {
+  let mut bytes = [0; 8];
+  inp.read_exact(&mut bytes)?;
+  return Ok(u64::from_le_bytes(bytes));
+}
+
+fn read_val(inp: &mut impl Read) -> Result<Val, SnapshotError> {
+  let mut bytes = [0; (Val::BITS / 8) as usize];
+  inp.read_exact(&mut bytes)?;
+  return Ok(Val::from_le_bytes(bytes));
+}
@@ -0,0 +1,261 @@
+// A net integrity validator
+// =========================
+//
+// This file implements a check of the invariants that the runtime relies on, but never verifies,
+// since it reads the heap with unchecked accesses. A corrupted net usually shows up much later,
+// as a '?' variable on readback, or as a crash. Validating after each step of a reduction finds
+// the step that broke it instead. With the 'validate' feature, the reducers do so after every
+// round, panicking on the first problem found.
+//
+// It also implements a check of a book, as a linker would do before running it: every REF must
+// have a definition, and expanding one must end. A definition whose root is a REF is an alias,
+// which 'deref' follows right away; if aliases form a cycle, expanding any of them never ends.
+
+use std::collections::HashMap;
+
+use crate::core::*;
+
+// A broken invariant of a net. Holders and targets of variables are shown as the variable that
+// would point to them: VRR for the root, or VR1/VR2 and a location for a port of a node. A side of
+// a redex is held by NIL.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Invalid {
+  // A variable whose target doesn't point back to where it is held.
+  Unmirrored { held: Ptr, var: Ptr, back: Ptr },
+  // A port of a live node that is empty.
+  NilPort { loc: Val, port: Port },
+  // The count of allocated nodes doesn't match the live nodes on the heap.
+  WrongUsed { used: usize, live: usize },
+  // A live node that no tree of the root or of a redex reaches, nor any other node.
+  Unreachable { loc: Val },
+  // A redex with a side that isn't a principal port.
+  BadRedex { a: Ptr, b: Ptr },
+  // A pointer to the main port of a node that was freed.
+  Dangling { held: Ptr, ptr: Ptr },
+  // A pointer to the main port of a node past the end of the heap.
+  OutOfBounds { held: Ptr, ptr: Ptr },
+}
+
+impl std::fmt::Display for Invalid {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Invalid::Unmirrored { held, var, back } => {
+        write!(f, "variable {:?} held at {:?} is mirrored by {:?}", var, held, back)
+      }
+      Invalid::NilPort { loc, port } => {
+        write!(f, "port {} of live node {} is empty", port + 1, loc)
+      }
+      Invalid::WrongUsed { used, live } => {
+        write!(f, "{} nodes are counted as used, but {} are live", used, live)
+      }
+      Invalid::Unreachable { loc } => {
+        write!(f, "live node {} is unreachable", loc)
+      }
+      Invalid::BadRedex { a, b } => {
+        write!(f, "redex {:?} ~ {:?} isn't between principal ports", a, b)
+      }
+      Invalid::Dangling { held, ptr } => {
+        write!(f, "pointer {:?} held at {:?} points to a freed node", ptr, held)
+      }
+      Invalid::OutOfBounds { held, ptr } => {
+        write!(f, "pointer {:?} held at {:?} points past the end of the heap", ptr, held)
+      }
+    }
+  }
+}
+
+impl std::error::Error for Invalid {}
+
+// A problem of a book. Definitions are named by the book's symbols, or as '#id' if they have none.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BookError {
+  // A definition with a REF to one that isn't on the book.
+  MissingRef { def: String, name: String },
+  // Definitions that are aliases of the next one, and the last of the first one.
+  AliasCycle { names: Vec<String> },
+  // A definition that isn't on an alias cycle, but is an alias of one that is, maybe indirectly.
+  EndlessHead { name: String },
+}
+
+impl std::fmt::Display for BookError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      BookError::MissingRef { def, name } => {
+        write!(f, "@{} refers to @{}, which isn't defined", def, name)
+      }
+      BookError::AliasCycle { names } => {
+        let names : Vec<String> = names.iter().chain(names.first()).map(|name| format!("@{}", name)).collect();
+        write!(f, "alias cycle: {}", names.join(" = "))
+      }
+      BookError::EndlessHead { name } => {
+        write!(f, "@{} is an alias of a cycle, so expanding it never ends", name)
+      }
+    }
+  }
+}
+
+impl std::error::Error for BookError {}
+
+impl Book {
+  // Checks that every REF of every definition has a definition, and that no alias expands
+  // forever. Returns every problem found: missing REFs, then alias cycles, then the aliases that
+  // lead to one, each by order of id.
+  pub fn check(&self) -> Result<(), Vec<BookError>> {
+    let ids : Vec<Val> = self.iter().map(|(id, _)| id).collect();
+    let mut errs = vec![];
+    for (def, got) in self.iter() {
+      let ports = got.node.iter().flat_map(|node| node.ports);
+      let acts = got.acts.iter().flat_map(|&(a, b)| [a, b]);
+      let mut missing : Vec<Val> = [got.root].into_iter().chain(acts).chain(ports).filter(|ptr| ptr.is_ref() && self.get(ptr.val()).is_none()).map(|ptr| ptr.val()).collect();
+      missing.sort();
+      missing.dedup();
+      errs.extend(missing.into_iter().map(|id| BookError::MissingRef { def: self.syms.show(def), name: self.syms.show(id) }));
+    }
+    // Follows the aliases of each definition, marking whether expanding it ends. A walk that comes
+    // back to one of its own definitions found a new cycle.
+    let mut ends : HashMap<Val, bool> = HashMap::new();
+    let mut loops = vec![];
+    for &def in &ids {
+      let mut path = vec![];
+      let mut id = def;
+      let done = loop {
+        if let Some(&done) = ends.get(&id) {
+          break done;
+        }
+        if let Some(at) = path.iter().position(|&other| other == id) {
+          loops.push(path[at ..].to_vec());
+          break false;
+        }
+        path.push(id);
+        match self.get(id) {
+          Some(got) if got.root.is_ref() => { id = got.root.val(); }
+          _                              => { break true; }
+        }
+      };
+      for id in path {
+        ends.insert(id, done);
+      }
+    }
+    for ids in &loops {
+      let min = ids.iter().enumerate().min_by_key(|&(_, id)| *id).map_or(0, |(at, _)| at);
+      let ids = [&ids[min ..], &ids[.. min]].concat();
+      errs.push(BookError::AliasCycle { names: ids.into_iter().map(|id| self.syms.show(id)).collect() });
+    }
+    for &id in &ids {
+      if !ends[&id] && !loops.iter().any(|ids| ids.contains(&id)) {
+        errs.push(BookError::EndlessHead { name: self.syms.show(id) });
+      }
+    }
+    return if errs.is_empty() { Ok(()) } else { Err(errs) };
+  }
+}
+
+impl Net {
+  // Checks that the net is well-formed: every variable is mirrored by its target, every pointer to a
+  // node points to a live one, live nodes have no empty port, 'used' counts the live nodes, every
+  // live node is reachable from the root or from a redex, other than vicious circles, and redexes
+  // are made of principal ports. Returns the first problem found.
+  pub fn validate(&self) -> Result<(), Invalid> {
+    let mut live = 0;
+    for (index, node) in self.node.iter().enumerate() {
+      if *node == Node::nil() {
+        continue;
+      }
+      live += 1;
+      for port in [P1, P2] {
+        if node.port(port).tag() == NIL {
+          return Err(Invalid::NilPort { loc: index as Val, port });
+        }
+      }
+    }
+    if live != self.used {
+      return Err(Invalid::WrongUsed { used: self.used, live });
+    }
+    for &(a, b) in &self.acts {
+      if !a.is_pri() || !b.is_pri() {
+        return Err(Invalid::BadRedex { a, b });
+      }
+    }
+    // Walks every tree, checking the variables on its leaves, and marking the nodes it reaches.
+    let mut seen = vec![false; self.node.len()];
+    let mut todo = vec![(Ptr::new(VRR, 0), self.root)];
+    for &(a, b) in &self.acts {
+      todo.push((Ptr::new(NIL, 0), a));
+      todo.push((Ptr::new(NIL, 0), b));
+    }
+    while let Some((held, ptr)) = todo.pop() {
+      if ptr.is_nod() {
+        self.check_node(held, ptr)?;
+        let loc = ptr.loc();
+        if !seen[loc as usize] {
+          seen[loc as usize] = true;
+          todo.push((Ptr::new(VR1, loc), self.get(loc, P1)));
+          todo.push((Ptr::new(VR2, loc), self.get(loc, P2)));
+        }
+      } else if ptr.is_var() {
+        let back = self.mirror(ptr);
+        if back != Some(held) {
+          return Err(Invalid::Unmirrored { held, var: ptr, back: back.unwrap_or(Ptr::new(NIL, 0)) });
+        }
+      }
+    }
+    // Nodes left are garbage. Only vicious circles, where each node hangs from another one, are a
+    // valid result of a reduction; a tree with no parent was lost by a rewrite.
+    let mut held = vec![false; self.node.len()];
+    for (index, node) in self.node.iter().enumerate() {
+      if *node == Node::nil() || seen[index] {
+        continue;
+      }
+      for port in [P1, P2] {
+        let ptr = *node.port(port);
+        let here = Ptr::new(if port == P1 { VR1 } else { VR2 }, index as Val);
+        if ptr.is_nod() {
+          self.check_node(here, ptr)?;
+          held[ptr.loc() as usize] = true;
+        } else if ptr.is_var() {
+          let back = self.mirror(ptr);
+          if back != Some(here) {
+            return Err(Invalid::Unmirrored { held: here, var: ptr, back: back.unwrap_or(Ptr::new(NIL, 0)) });
+          }
+        }
+      }
+    }
+    for (index, node) in self.node.iter().enumerate() {
+      if *node != Node::nil() && !seen[index] && !held[index] {
+        return Err(Invalid::Unreachable { loc: index as Val });
+      }
+    }
+    return Ok(());
+  }
+
+  // Checks that a pointer to a node, held at 'held', points to a live node on the heap.
+  fn check_node(&self, held: Ptr, ptr: Ptr) -> Result<(), Invalid> {
+    if ptr.loc() as usize >= self.node.len() {
+      return Err(Invalid::OutOfBounds { held, ptr });
+    }
+    if *self.at(ptr.loc()) == Node::nil() {
+      return Err(Invalid::Dangling { held, ptr });
+    }
+    return Ok(());
+  }
+
+  // Reads the pointer stored where a variable points to, if that is in the net.
+  fn mirror(&self, var: Ptr) -> Option<Ptr> {
+    return match var.tag() {
+      VRR => Some(self.root),
+      VR1 if (var.loc() as usize) < self.node.len() => Some(self.get(var.loc(), P1)),
+      VR2 if (var.loc() as usize) < self.node.len() => Some(self.get(var.loc(), P2)),
+      _   => None,
+    };
+  }
+
+  // Panics if the net isn't well-formed, when built with the 'validate' feature. Called by the
+  // reducers after every round.
+  #[inline(always)]
+  pub(crate) fn debug_validate(&self) {
+    #[cfg(feature = "validate")]
+    if let Err(err) = self.validate() {
+      panic!("invalid net after round {}: {}", self.stats.rnds, err);
+    }
+  }
+}
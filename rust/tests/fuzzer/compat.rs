@@ -1,4 +1,4 @@
-use hvm_core::{LNet, LTree, Tag};
+use hvm_core::{Lab, LNet, LTree, Ptr, Val, OP2, OPR, USE};
 use std::collections::HashMap;
 
 #[derive(Clone, Debug)]
@@ -167,22 +167,36 @@ fn compat_tree_to_hvm_tree(
 ) -> LTree {
     let kind = kind(inet, root);
     let tag = kind & TAG_MASK;
-    let label = kind & LABEL_MASK; // TODO: Check if label too high, do something about it.
+    let label = kind & LABEL_MASK;
     match tag {
         ERA => LTree::Era,
         CON => LTree::Nod {
-            tag: hvm_core::CON,
+            tag: hvm_core::CTR,
+            lab: hvm_core::CON,
             lft: Box::new(var_or_subtree(inet, port(root, 1), port_to_var_id)),
             rgt: Box::new(var_or_subtree(inet, port(root, 2), port_to_var_id)),
         },
         DUP => LTree::Nod {
-            tag: hvm_core::DUP + label as Tag,
+            tag: hvm_core::CTR,
+            lab: hvm_core::DUP + label as Lab,
             lft: Box::new(var_or_subtree(inet, port(root, 1), port_to_var_id)),
             rgt: Box::new(var_or_subtree(inet, port(root, 2), port_to_var_id)),
         },
-        REF => LTree::Ref { nam: label },
-        NUM => LTree::NUM { val: label },
-        NUMOP => todo!(),
+        REF => LTree::Ref { nam: label.to_string() },
+        NUM => LTree::NUM { val: Ptr::new_num(USE, label as Val).val() },
+        // The label is the operator, given to the left operand before the right one comes, as in
+        // 'a ~ <[opr] <b r>>'.
+        NUMOP => LTree::Nod {
+            tag: OP2,
+            lab: 0,
+            lft: Box::new(LTree::NUM { val: Ptr::new_num(OPR, label as Val).val() }),
+            rgt: Box::new(LTree::Nod {
+                tag: OP2,
+                lab: 0,
+                lft: Box::new(var_or_subtree(inet, port(root, 1), port_to_var_id)),
+                rgt: Box::new(var_or_subtree(inet, port(root, 2), port_to_var_id)),
+            }),
+        },
         _ => unreachable!("Invalid tag in compat tree {tag:x}"),
     }
 }
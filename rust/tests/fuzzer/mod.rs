@@ -2,7 +2,7 @@ mod compat;
 
 use compat::CompatError;
 use hvm_core::{
-    core::Net,
+    core::{Net, Symbols, ADD, AND, DIV, EQ, GT, LT, MOD, MUL, NE, OR, SHL, SHR, SUB, XOR},
     lang::{lnet_to_net, LNet},
 };
 use quickcheck::Arbitrary;
@@ -23,14 +23,16 @@ pub struct Address {
 #[derive(Clone, Debug, Copy)]
 pub enum NodeTag {
     Era,
-    Con { tag: u16 },
+    Con,
     Dup { tag: u16 },
+    Num { val: u16 },
+    Op { opr: u8 },
 }
 
 impl NodeTag {
     fn arity(&self) -> usize {
         match self {
-            NodeTag::Era => 1,
+            NodeTag::Era | NodeTag::Num { .. } => 1,
             _ => 3,
         }
     }
@@ -39,13 +41,17 @@ impl NodeTag {
 impl Arbitrary for NodeTag {
     fn arbitrary(g: &mut quickcheck::Gen) -> Self {
         let era = NodeTag::Era;
-        let con = NodeTag::Con {
-            tag: u16::arbitrary(g),
-        };
+        let con = NodeTag::Con;
         let dup = NodeTag::Dup {
             tag: u16::arbitrary(g),
         };
-        *g.choose(&[era, con, dup]).unwrap()
+        let num = NodeTag::Num {
+            val: u16::arbitrary(g),
+        };
+        let op = NodeTag::Op {
+            opr: *g.choose(&[ADD, SUB, MUL, DIV, MOD, EQ, NE, LT, GT, AND, OR, XOR, SHL, SHR]).unwrap() as u8,
+        };
+        *g.choose(&[era, con, dup, num, op]).unwrap()
     }
 }
 
@@ -65,10 +71,10 @@ impl Arbitrary for SimpleNet {
             nodes.push(Arbitrary::arbitrary(g));
             nodes.push(Arbitrary::arbitrary(g));
         }
-        if number_of_ports(&nodes) % 2 != 0 {
+        if !number_of_ports(&nodes).is_multiple_of(2) {
             nodes.push(Arbitrary::arbitrary(g));
         }
-        assert!(number_of_ports(&nodes) % 2 == 0);
+        assert!(number_of_ports(&nodes).is_multiple_of(2));
 
         let mut addresses: Vec<[Option<Address>; 3]> = vec![[None; 3]; nodes.len()];
 
@@ -86,7 +92,7 @@ impl Arbitrary for SimpleNet {
         }
 
         fn find_free_port(
-            nodes: &Vec<NodeTag>,
+            nodes: &[NodeTag],
             addresses: &[[Option<Address>; 3]],
             g: &mut quickcheck::Gen,
             (curr_idx, curr_port): (usize, usize),
@@ -152,7 +158,7 @@ impl TryFrom<SimpleNet> for LNet {
     type Error = CompatError;
 
     fn try_from(SimpleNet(net): SimpleNet) -> Result<Self, Self::Error> {
-        use compat::{compat_net_to_core, port, INet, NodeKind, Port, CON, DUP, ERA};
+        use compat::{compat_net_to_core, port, INet, NodeKind, Port, CON, DUP, ERA, NUM, NUMOP};
         let mut nodes = Vec::with_capacity(net.len() * 4);
         for (tag, [main, aux1, aux2]) in net {
             fn convert_address(address: Address) -> Port {
@@ -161,8 +167,10 @@ impl TryFrom<SimpleNet> for LNet {
             fn convert_tag(tag: NodeTag) -> NodeKind {
                 match tag {
                     NodeTag::Era => ERA,
-                    NodeTag::Con { .. } => CON,
-                    NodeTag::Dup { .. } => DUP,
+                    NodeTag::Con => CON,
+                    NodeTag::Dup { tag } => DUP | tag as NodeKind,
+                    NodeTag::Num { val } => NUM | val as NodeKind,
+                    NodeTag::Op { opr } => NUMOP | opr as NodeKind,
                 }
             }
             nodes.push(convert_address(main));
@@ -196,7 +204,7 @@ impl Arbitrary for NetWrapper {
         loop {
             let simple_net = SimpleNet::arbitrary(g);
             match LNet::try_from(simple_net.clone()) {
-                Ok(lnet) => return lnet_to_net(&lnet, 4).into(),
+                Ok(lnet) => return lnet_to_net(&lnet, 4, &mut Symbols::default()).unwrap().into(),
                 Err(_) => continue,
             }
         }
@@ -1,16 +1,889 @@
 mod fuzzer;
 
 use fuzzer::NetWrapper as Net;
-use hvm_core::core::Book;
+use hvm_core::core::Net as CoreNet;
+use hvm_core::core::{Book, Budget, Node, Outcome, Progress, Ptr, ReductionObserver, Rewrite, RuntimeError, Stats, SymbolError, Val, Worker, ADD, CTR, ERA, REF, Lab, NIL, NUM_BITS, NUM_MASK, OP2, P1, P2, VAL_MASK, VR1, VRR};
+use hvm_core::snapshot::{from_cuda, to_cuda, SnapshotError};
+use hvm_core::validate::{BookError, Invalid};
+use hvm_core::lang::{define, try_define, do_parse_lnet, show_net, show_net_with, ParseError};
 use quickcheck_macros::quickcheck;
 
 const MAX_STEPS: usize = 1000;
 
+// A book whose 'main' builds a complete binary tree of depth 12, with 'c12' applied to 'g_s' and
+// 'g_z'.
+fn tree_book() -> Book {
+    let mut book = Book::new();
+    define(&mut book, "c12", "$ (0 (1 (1 (1 (1 (1 (1 (1 (1 (1 (1 (1 (0 l k) (0 k j)) (0 j i)) (0 i h)) (0 h g)) (0 g f)) (0 f e)) (0 e d)) (0 d c)) (0 c b)) (0 b a)) (0 a R)) (0 l R))");
+    define(&mut book, "g_s", "$ (0 (2 r0 r1) (0 (0 r0 (0 r1 r)) r))");
+    define(&mut book, "g_z", "$ (0 x x)");
+    define(&mut book, "main", "$ root & @c12 ~ (0 @g_s (0 @g_z root))");
+    book
+}
+
+// Reduces a net within a budget on the reducer named by 'mode': "seq", "par" (with 4 threads),
+// "dfs", "lazy", or "whnf" (to weak head normal form).
+fn run_mode(net: &mut CoreNet, book: &Book, mode: &str, budget: Budget) -> Result<(Outcome, Stats), RuntimeError> {
+    match mode {
+        "seq" => net.normal(book, budget),
+        "par" => net.normal_par(book, budget, 4),
+        "dfs" => net.normal_dfs(book, budget),
+        "lazy" => net.normal_lazy(book, budget),
+        _ => net.whnf(book, budget),
+    }
+}
+
 #[quickcheck]
 fn prop_reduces_or_stops(Net(net): Net) -> bool {
     let mut net = net;
-    net.normal(&Book::new(), Some(MAX_STEPS));
-    net.rwts >= MAX_STEPS || net.acts.is_empty()
+    match net.normal(&Book::new(), Budget::steps(MAX_STEPS)) {
+        Ok((Outcome::Normal, _)) => net.acts.is_empty(),
+        Ok((Outcome::OutOfFuel, _)) => net.rwts >= MAX_STEPS && !net.acts.is_empty(),
+        _ => false,
+    }
+}
+
+#[quickcheck]
+fn prop_parallel_matches_sequential(Net(net): Net) -> bool {
+    let mut seq = net.clone();
+    seq.normal(&Book::new(), Budget::steps(MAX_STEPS)).unwrap();
+    let mut par = net;
+    par.normal_par(&Book::new(), Budget::steps(MAX_STEPS), 4).unwrap();
+    // Workers only check the step limit now and then, so a net that runs out of fuel may stop at
+    // another point. Any other net reaches the same normal form on both, with the same rewrites.
+    let finished = seq.acts.is_empty() && par.acts.is_empty();
+    if !finished && seq.rwts >= MAX_STEPS {
+        return par.rwts >= MAX_STEPS;
+    }
+    finished && seq.rwts == par.rwts && seq.used == par.used && show_net(&seq) == show_net(&par)
+}
+
+#[quickcheck]
+fn prop_depth_first_matches_rounds(Net(net): Net) -> bool {
+    let mut seq = net.clone();
+    seq.normal(&Book::new(), Budget::steps(MAX_STEPS)).unwrap();
+    let mut dfs = net;
+    dfs.normal_dfs(&Book::new(), Budget::steps(MAX_STEPS)).unwrap();
+    if !seq.acts.is_empty() || !dfs.acts.is_empty() {
+        return seq.rwts >= MAX_STEPS && dfs.rwts >= MAX_STEPS;
+    }
+    seq.rwts == dfs.rwts && seq.used == dfs.used && show_net(&seq) == show_net(&dfs)
+}
+
+#[test]
+fn parallel_big_tree() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+
+    let mut seq = CoreNet::new(1 << 18);
+    seq.boot(main);
+    seq.normal(book, Budget::default()).unwrap();
+    let mut par = CoreNet::new(1 << 18);
+    par.boot(main);
+    par.normal_par(book, Budget::default(), 4).unwrap();
+    let mut dfs = CoreNet::new(1 << 18);
+    dfs.boot(main);
+    dfs.normal_dfs(book, Budget::default()).unwrap();
+
+    assert_eq!(seq.rwts, par.rwts);
+    assert_eq!(seq.used, par.used);
+    assert_eq!(show_net(&seq), show_net(&par));
+    assert_eq!(seq.rwts, dfs.rwts);
+    assert_eq!(show_net(&seq), show_net(&dfs));
+}
+
+#[test]
+fn alloc_reuses_freed_nodes() {
+    let mut net = CoreNet::new(8);
+    assert_eq!(net.alloc_chunk(4), Some(0));
+    assert_eq!(net.alloc(), 4);
+    assert_eq!(net.alloc_chunk(4), None);
+    net.free(1);
+    assert_eq!(net.alloc_chunk(3), Some(5));
+    assert_eq!(net.alloc(), 1);
+    assert_eq!(net.used, 8);
+}
+
+#[test]
+fn heap_grows_up_to_limit() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+
+    // The normal form needs a bigger heap than the initial one, but not the whole limit.
+    let mut net = CoreNet::new(1 << 18);
+    net.boot(main);
+    net.normal(book, Budget::default()).unwrap();
+    assert_eq!(net.used, 16381);
+    assert!(net.node.len() >= 16381 && net.node.len() < 1 << 18);
+
+    // With a lower limit, both reducers run out of memory, keeping the pending redexes.
+    for threads in [0, 1, 4] {
+        let mut net = CoreNet::new(1 << 12);
+        net.boot(main);
+        let done = if threads == 0 { net.normal(book, Budget::default()) } else { net.normal_par(book, Budget::default(), threads) };
+        assert_eq!(done.unwrap().0, Outcome::OutOfMemory);
+        assert!(!net.acts.is_empty() && net.used <= 1 << 12);
+    }
+}
+
+#[test]
+fn reduction_budgets() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+    let mut expected = CoreNet::new(1 << 18);
+    expected.boot(main);
+    assert_eq!(expected.normal(book, Budget::default()).unwrap().0, Outcome::Normal);
+
+    // Each limit stops every reducer with its own outcome, and a larger budget then finishes the job.
+    let budgets = [
+        (Budget::steps(5000), Outcome::OutOfFuel),
+        (Budget { node: Some(1 << 12), ..Budget::default() }, Outcome::OutOfMemory),
+        (Budget { time: Some(std::time::Duration::ZERO), ..Budget::default() }, Outcome::Timeout),
+    ];
+    for (budget, outcome) in budgets {
+        for mode in ["seq", "par", "dfs", "lazy"] {
+            let mut net = CoreNet::new(1 << 18);
+            net.boot(main);
+            let run = |net: &mut CoreNet, budget| run_mode(net, book, mode, budget).unwrap().0;
+            assert_eq!(run(&mut net, budget), outcome, "{}", mode);
+            assert!(!net.acts.is_empty() && net.used <= budget.node.unwrap_or(net.limit), "{}", mode);
+            assert!(budget.rwts.is_none_or(|max| net.rwts <= max), "{}", mode);
+            assert_eq!(net.limit, 1 << 18);
+            assert_eq!(run(&mut net, Budget::default()), Outcome::Normal, "{}", mode);
+            assert_eq!(show_net(&net), show_net(&expected), "{}", mode);
+        }
+    }
+}
+
+#[test]
+fn reduction_in_slices() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+    let mut expected = CoreNet::new(1 << 18);
+    expected.boot(main);
+    expected.normal(book, Budget::default()).unwrap();
+
+    // Slices never go over their rewrites, and together do the same work as a single run.
+    for size in [1, 100, 5000] {
+        let mut net = CoreNet::new(1 << 18);
+        net.boot(main);
+        let mut slices = 0;
+        loop {
+            let rwts = net.rwts;
+            let progress = net.step_for(book, size).unwrap();
+            assert!(net.rwts - rwts <= size);
+            slices += 1;
+            if progress == Progress::Done {
+                break;
+            }
+        }
+        assert_eq!((net.rwts, net.used), (expected.rwts, expected.used));
+        assert_eq!(show_net(&net), show_net(&expected));
+        assert_eq!(slices, expected.rwts.div_ceil(size));
+    }
+}
+
+#[test]
+fn validate_finds_corruption() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+
+    // Nets stay valid between slices of a reduction, on every reducer.
+    let mut net = CoreNet::new(1 << 18);
+    net.boot(main);
+    while net.step_for(book, 1000).unwrap() == Progress::Pending {
+        assert_eq!(net.validate(), Ok(()));
+    }
+    for budget in [Budget::steps(3000), Budget::default()] {
+        let mut par = CoreNet::new(1 << 18);
+        par.boot(main);
+        par.normal_par(book, budget, 4).unwrap();
+        assert_eq!(par.validate(), Ok(()));
+        let mut dfs = CoreNet::new(1 << 18);
+        dfs.boot(main);
+        dfs.normal_dfs(book, budget).unwrap();
+        assert_eq!(dfs.validate(), Ok(()));
+    }
+
+    // Each kind of corruption is reported.
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    net.step_for(book, 10).unwrap();
+    assert_eq!(net.validate(), Ok(()));
+    let (a, b) = net.acts[0];
+    let loc = (0 .. net.used as Val).find(|&loc| net.get(loc, P1).is_var()).unwrap();
+    let var = net.get(loc, P1);
+
+    let mut bad = net.clone();
+    bad.set(loc, P2, Ptr::new(NIL, 0));
+    assert_eq!(bad.validate(), Err(Invalid::NilPort { loc, port: P2 }));
+    let mut bad = net.clone();
+    bad.used += 1;
+    assert_eq!(bad.validate(), Err(Invalid::WrongUsed { used: net.used + 1, live: net.used }));
+    let mut bad = net.clone();
+    bad.acts.push((var, b));
+    assert_eq!(bad.validate(), Err(Invalid::BadRedex { a: var, b }));
+    let mut bad = net.clone();
+    bad.set(var.loc(), if var.tag() == VR1 { P1 } else { P2 }, Ptr::new(ERA, 0));
+    assert!(matches!(bad.validate(), Err(Invalid::Unmirrored { .. })));
+    let mut bad = net.clone();
+    bad.acts.retain(|&redex| redex != (a, b));
+    assert!(matches!(bad.validate(), Err(Invalid::Unmirrored { .. } | Invalid::Unreachable { .. })));
+    let mut bad = net.clone();
+    let stray = bad.alloc();
+    bad.set(stray, P1, Ptr::new(ERA, 0));
+    bad.set(stray, P2, Ptr::new(ERA, 0));
+    assert_eq!(bad.validate(), Err(Invalid::Unreachable { loc: stray }));
+    let free = Ptr::new(CTR, (0 .. net.node.len() as Val).find(|&loc| *net.at(loc) == Node::nil()).unwrap());
+    let mut bad = net.clone();
+    bad.acts[0] = (free, b);
+    assert_eq!(bad.validate(), Err(Invalid::Dangling { held: Ptr::new(NIL, 0), ptr: free }));
+    let past = Ptr::new(CTR, net.node.len() as Val);
+    let mut bad = net.clone();
+    bad.acts[0] = (past, b);
+    assert_eq!(bad.validate(), Err(Invalid::OutOfBounds { held: Ptr::new(NIL, 0), ptr: past }));
+}
+
+#[test]
+fn snapshot_resumes() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+
+    // A net saved halfway keeps its state, and reduces to the same result as the original.
+    let mut net = CoreNet::new(1 << 18);
+    net.boot(main);
+    net.step_for(book, 5000).unwrap();
+    let mut file = vec![];
+    net.save(&mut file).unwrap();
+    let mut back = CoreNet::load(&mut &file[..]).unwrap();
+    assert_eq!((back.root, &back.acts, back.used, back.rwts, back.stats, back.limit), (net.root, &net.acts, net.used, net.rwts, net.stats, net.limit));
+    assert_eq!(back.validate(), Ok(()));
+    assert_eq!(&file[0 .. 4], b"HVMN");
+    assert_eq!(u32::from_le_bytes(file[8 .. 12].try_into().unwrap()), to_cuda(net.root, 0).unwrap());
+    net.normal(book, Budget::default()).unwrap();
+    back.normal(book, Budget::default()).unwrap();
+    assert_eq!((back.rwts, back.used), (net.rwts, net.used));
+    assert_eq!(show_net(&back), show_net(&net));
+
+    // Pointers map to the CUDA layout, where constructors are labelled by their tag.
+    assert_eq!(to_cuda(Ptr::new(CTR, 5), 1), Some(0xB000_0005));
+    assert_eq!(from_cuda(0xB000_0005), Some((Ptr::new(CTR, 5), 1)));
+    assert_eq!(to_cuda(Ptr::new(CTR, 5), 3), None);
+    assert_eq!(to_cuda(Ptr::new(OP2, 5), 0), None);
+    assert_eq!(from_cuda(0xF000_0000), None);
+
+    // Broken files are rejected.
+    assert!(matches!(CoreNet::load(&mut &b"HVMX"[..]), Err(SnapshotError::BadMagic)));
+    assert!(matches!(CoreNet::load(&mut &file[.. file.len() - 1]), Err(SnapshotError::Io(_))));
+    let corrupt = |root: u32| [&file[.. 8], &root.to_le_bytes(), &file[12 ..]].concat();
+    assert!(matches!(CoreNet::load(&mut &corrupt(0xA000_0000 | 0xFFF_FFFF)[..]), Err(SnapshotError::BadPointer { .. })));
+    assert!(matches!(CoreNet::load(&mut &corrupt(0x2000_0000)[..]), Err(SnapshotError::Invalid(_))));
+
+    // Numbers with an operator have no CUDA equivalent, and CUDA numbers may not fit this build.
+    assert_eq!(to_cuda(Ptr::new_num(ADD, 1), 0), None);
+    assert_eq!(from_cuda(0x9FFF_FFFF).is_none(), NUM_MASK < 0xFFF_FFFF);
+
+    // Nets with nodes the CUDA layout can't hold, such as numeric ones or labels above 2, are saved
+    // in the native layout instead, and resume just as well.
+    let main = define(book, "nums", "$ (0 r s) & 4 ~ <[*] <3 r>> & (5 1 2) ~ (3 s *)");
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    net.step_for(book, 2).unwrap();
+    let mut file = vec![];
+    net.save(&mut file).unwrap();
+    assert_eq!(u32::from_le_bytes(file[4 .. 8].try_into().unwrap()), 2);
+    let mut back = CoreNet::load(&mut &file[..]).unwrap();
+    assert_eq!((back.root, &back.acts, back.used, back.rwts), (net.root, &net.acts, net.used, net.rwts));
+    net.normal(book, Budget::default()).unwrap();
+    back.normal(book, Budget::default()).unwrap();
+    assert_eq!(show_net(&net), "$ (0 12 (5 1 2))\n");
+    assert_eq!(show_net(&back), show_net(&net));
+}
+
+#[test]
+fn compiled_book() {
+    let book = &mut tree_book();
+    define(book, "inc", "$ (0 <[+1] r> r)");
+    define(book, "main", "$ (0 a b) & @inc ~ (7 (0 5 a) (0 6 b))");
+    define(book, "tree", "$ root & @c12 ~ (0 @g_s (0 @g_z root))");
+
+    // Loaded definitions are the same nets, and reduce the same.
+    let mut file = vec![];
+    book.save(&mut file).unwrap();
+    let back = Book::load(&mut &file[..]).unwrap();
+    assert_eq!(back.defs, book.defs);
+    for name in ["main", "tree"] {
+        let mut net = CoreNet::new(1 << 18);
+        net.boot(book.syms.id(name).unwrap());
+        net.normal(book, Budget::default()).unwrap();
+        let mut got = CoreNet::new(1 << 18);
+        got.boot(back.syms.id(name).unwrap());
+        got.normal(&back, Budget::default()).unwrap();
+        assert_eq!((got.rwts, show_net(&got)), (net.rwts, show_net(&net)));
+    }
+
+    // Saving is deterministic, and broken files are rejected.
+    let mut again = vec![];
+    back.save(&mut again).unwrap();
+    assert_eq!(again, file);
+    assert!(matches!(Book::load(&mut &b"HVMN"[..]), Err(SnapshotError::BadMagic)));
+    assert!(matches!(Book::load(&mut &file[.. file.len() - 1]), Err(SnapshotError::Io(_))));
+    let mut wide = file.clone();
+    wide[8] ^= 32 ^ 64;
+    assert!(matches!(Book::load(&mut &wide[..]), Err(SnapshotError::BadWidth { .. })));
+
+    // Lengths, ids and definitions are checked before they are used: a huge name length reads no
+    // further than the file, an id past the count of entries is out of range, and a definition
+    // can't point past its own nodes.
+    let head = &file[.. 12];
+    let sym = |id: Val, len: u32| [head, &1u64.to_le_bytes(), &id.to_le_bytes(), &len.to_le_bytes(), b"x", &0u64.to_le_bytes()].concat();
+    assert!(matches!(Book::load(&mut &sym(0, u32::MAX)[..]), Err(SnapshotError::Io(_))));
+    assert!(matches!(Book::load(&mut &sym(VAL_MASK, 1)[..]), Err(SnapshotError::BadId { id: VAL_MASK })));
+    assert!(Book::load(&mut &sym(0, 1)[..]).is_ok());
+    let def = |id: Val, root: Ptr| [head, &0u64.to_le_bytes(), &1u64.to_le_bytes(), &id.to_le_bytes(), &root.data.to_le_bytes(), &0u64.to_le_bytes(), &0u64.to_le_bytes()].concat();
+    assert!(matches!(Book::load(&mut &def(VAL_MASK, Ptr::new(ERA, 0))[..]), Err(SnapshotError::BadId { id: VAL_MASK })));
+    let bad = Ptr::new(CTR, 0);
+    assert!(matches!(Book::load(&mut &def(0, bad)[..]), Err(SnapshotError::BadDef { id: 0, err: RuntimeError::BadPointer { ptr } }) if ptr == bad));
+    assert!(Book::load(&mut &def(0, Ptr::new(ERA, 0))[..]).is_ok());
+}
+
+#[test]
+fn book_check() {
+    let book = &mut Book::new();
+    define(book, "id", "$ (0 x x)");
+    define(book, "a", "$ @b");
+    define(book, "b", "$ @a");
+    define(book, "c", "$ @a");
+    define(book, "d", "$ (0 @id r) & @nope ~ (0 @gone r)");
+    define(book, "e", "$ @id");
+    let [a, b, c, d, nope] = ["a", "b", "c", "d", "nope"].map(|name| book.syms.id(name).unwrap());
+    let name = |name: &str| name.to_string();
+    let errs = book.check().unwrap_err();
+    assert_eq!(errs, vec![
+        BookError::MissingRef { def: name("d"), name: name("nope") },
+        BookError::MissingRef { def: name("d"), name: name("gone") },
+        BookError::AliasCycle { names: vec![name("a"), name("b")] },
+        BookError::EndlessHead { name: name("c") },
+    ]);
+    assert_eq!(errs[2].to_string(), "alias cycle: @a = @b = @a");
+
+    // Reducers fail on these, instead of hanging.
+    for name in ["c", "d"] {
+        for mode in ["seq", "par", "dfs"] {
+            let mut net = CoreNet::new(1 << 12);
+            net.boot(book.syms.id(name).unwrap());
+            let got = run_mode(&mut net, book, mode, Budget::default());
+            match name {
+                "c" => assert!(matches!(got, Err(RuntimeError::AliasCycle { id }) if id == a || id == b), "{}: {:?}", mode, got),
+                _ => assert_eq!(got, Err(RuntimeError::MissingDef { id: nope }), "{}", mode),
+            }
+        }
+    }
+    for id in [a, c, d] {
+        book.undef(id);
+    }
+    assert_eq!(book.check(), Err(vec![BookError::MissingRef { def: name("b"), name: name("a") }]));
+    define(book, "a", "$ (0 x x)");
+    assert_eq!(book.check(), Ok(()));
+}
+
+#[test]
+fn runtime_errors() {
+    let book = &mut Book::new();
+    define(book, "g_z", "$ (0 x x)");
+    let main = define(book, "main", "$ root & @g_z ~ (0 @nope root)");
+
+    // A missing definition stops the reduction, instead of hanging or crashing.
+    let id = book.syms.id("nope").unwrap();
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    assert_eq!(net.normal(book, Budget::default()), Err(RuntimeError::MissingDef { id }));
+    let mut dfs = CoreNet::new(1 << 12);
+    dfs.boot(main);
+    assert_eq!(dfs.normal_dfs(book, Budget::default()), Err(RuntimeError::MissingDef { id }));
+
+    // With bounds checks, so does a malformed redex.
+    #[cfg(feature = "checked")]
+    {
+        let mut net = CoreNet::new(1 << 12);
+        net.acts.push((Ptr::new(VR1, 0), Ptr::new(ERA, 0)));
+        assert_eq!(net.normal(book, Budget::default()), Err(RuntimeError::BadTag { ptr: Ptr::new(VR1, 0) }));
+        let far = Ptr::new(CTR, 1 << 20);
+        let mut net = CoreNet::new(1 << 12);
+        net.acts.push((far, Ptr::new(ERA, 0)));
+        assert_eq!(net.normal(book, Budget::default()), Err(RuntimeError::BadPointer { ptr: far }));
+        let mut par = CoreNet::new(1 << 12);
+        par.acts.push((far, Ptr::new(ERA, 0)));
+        assert_eq!(par.normal_par(book, Budget::default(), 4), Err(RuntimeError::BadPointer { ptr: far }));
+    }
+}
+
+#[test]
+fn compact_keeps_net() {
+    let book = &mut tree_book();
+    let main = book.syms.id("main").unwrap();
+    let mut expected = CoreNet::new(1 << 18);
+    expected.boot(main);
+    expected.normal(book, Budget::default()).unwrap();
+
+    // Compacting between steps keeps the same net, with its nodes on a dense prefix.
+    let mut net = CoreNet::new(1 << 18);
+    net.boot(main);
+    for step in 1 .. {
+        net.normal(book, Budget::steps(step * 5000)).unwrap();
+        let before = show_net(&net);
+        net.compact(step % 2 == 0);
+        assert_eq!(show_net(&net), before);
+        assert!(net.node[.. net.used].iter().all(|node| *node != Node::nil()));
+        assert!(net.node[net.used ..].iter().all(|node| *node == Node::nil()));
+        if net.acts.is_empty() {
+            break;
+        }
+    }
+    assert_eq!(show_net(&net), show_net(&expected));
+    assert!(net.node.len() < expected.node.len());
+}
+
+#[test]
+fn lazy_reduction() {
+    let book = &mut tree_book();
+    define(book, "tree", "$ root & @c12 ~ (0 @g_s (0 @g_z root))");
+    define(book, "junk", "$ * & @c12 ~ (0 @g_s (0 @g_z *))");
+    let mut run = |code: &str, mode: &str| {
+        let main = define(book, "main", code);
+        let mut net = CoreNet::new(1 << 18);
+        net.boot(main);
+        run_mode(&mut net, book, mode, Budget::default()).unwrap();
+        (show_net_with(&net, &book.syms), net.rwts)
+    };
+
+    // Lazy reduction gets the same normal form, without reducing what the root doesn't use.
+    let code = "$ r & @junk ~ (0 a a) & (0 x x) ~ (0 (0 @tree 7) r)";
+    let tree = run("$ @tree", "seq").0;
+    let (strict, strict_rwts) = run(code, "seq");
+    let (lazy, lazy_rwts) = run(code, "lazy");
+    assert_eq!(strict, format!("$ (0 {} 7)\n", &tree[2 .. tree.len() - 1]));
+    assert!(lazy.starts_with(&strict) && lazy.contains("& @junk\n"));
+    assert!(lazy_rwts < strict_rwts / 2);
+
+    // Weak head normal form stops once the root is on a main port.
+    assert_eq!(run(code, "whnf").0, "$ (0 @tree 7)\n& @junk\n~ (0 b b)\n");
+}
+
+#[test]
+fn deep_trees() {
+    // Expanding, reducing and reading back a net don't recurse on the depth of its trees, which can
+    // be far deeper than the stack of a thread.
+    let book = &mut Book::new();
+    let era = define(book, "era", "$ *");
+    let depth = 1 << 16;
+    let mut net = CoreNet::new(depth);
+    let locs: Vec<Val> = (0 .. depth).map(|_| net.alloc()).collect();
+    net.root = Ptr::new(CTR, locs[0]);
+    for i in 0 .. depth {
+        let next = if i + 1 < depth { Ptr::new(CTR, locs[i + 1]) } else { Ptr::new(REF, era) };
+        net.set(locs[i], P1, Ptr::new(ERA, 0));
+        net.set(locs[i], P2, next);
+    }
+    net.normal(book, Budget::default()).unwrap();
+    assert_eq!(show_net(&net), format!("$ {}*{}\n", "(0 * ".repeat(depth), ")".repeat(depth)));
+}
+
+#[test]
+fn deref_big_definition() {
+    // A tree of 2^17 - 1 nodes, bigger than any fixed scratch space would be.
+    fn tree(depth: usize) -> String {
+        if depth == 0 { "*".to_string() } else { format!("(0 {} {})", tree(depth - 1), tree(depth - 1)) }
+    }
+    let book = &mut Book::new();
+    let big = define(book, "big", &format!("$ {}", tree(17)));
+    let main = define(book, "main", "$ root & @big ~ (0 @big root)");
+
+    let mut net = CoreNet::new(1 << 20);
+    net.boot(big);
+    net.expand(book, Ptr::new(VRR, 0)).unwrap();
+    assert_eq!(net.used, (1 << 17) - 1);
+
+    for threads in [0, 1, 4] {
+        let mut net = CoreNet::new(1 << 20);
+        net.boot(main);
+        let done = if threads == 0 { net.normal(book, Budget::default()) } else { net.normal_par(book, Budget::default(), threads) };
+        done.unwrap();
+        assert_eq!(net.used, (1 << 16) - 1);
+    }
+}
+
+#[test]
+fn numeric_operations() {
+    let book = &mut Book::new();
+    define(book, "inc", "$ (0 <[+1] r> r)");
+    let max = NUM_MASK.to_string();
+    let cases = [
+        ("$ r & 5 ~ <[-1] r>", "4"),
+        ("$ r & 0 ~ <[-1] r>", max.as_str()),
+        ("$ r & 2 ~ <[<3] r>", "1"),
+        ("$ r & 2 ~ <[/0] r>", "0"),
+        ("$ r & 2 ~ <[-] r>", "[-2]"),
+        ("$ r & 3 ~ <[+] <4 r>>", "7"),
+        ("$ r & [-10] ~ <y r> & 3 ~ <[*2] y>", "4"),
+        ("$ r & 7 ~ (1 <[*] t> <t r>)", "49"),
+        ("$ r & @inc ~ (0 5 r)", "6"),
+        ("$ (0 a b) & @inc ~ (1 (0 5 a) (0 6 b))", "(0 6 7)"),
+        ("$ r & * ~ <1 r>", "*"),
+    ];
+    for (code, result) in cases {
+        let main = define(book, "main", code);
+        for mode in ["seq", "par", "dfs"] {
+            let mut net = CoreNet::new(1 << 12);
+            net.boot(main);
+            run_mode(&mut net, book, mode, Budget::default()).unwrap();
+            assert_eq!(show_net(&net), format!("$ {}\n", result), "{} ({})", code, mode);
+        }
+    }
+    // Literals that don't fit on a number are a parse error, rather than another number.
+    let big = (NUM_MASK as u128 + 1).to_string();
+    for code in [format!("$ {}", big), format!("$ [+{}]", big), "$ 99999999999999999999999".to_string()] {
+        assert!(matches!(do_parse_lnet(&code), Err(ParseError::BigNumber { .. })), "{}", code);
+    }
+    assert!(matches!(do_parse_lnet("$ [=5]"), Err(ParseError::BadOperator { .. })));
+}
+
+#[test]
+fn reduction_stats() {
+    // Every rewrite is counted by exactly one rule, on all reducers.
+    let book = &mut tree_book();
+    let main = define(book, "main", "$ r & @c12 ~ (0 @g_s (0 @g_z r)) & @c12 ~ *");
+    for mode in ["seq", "par", "dfs"] {
+        let mut net = CoreNet::new(1 << 18);
+        net.boot(main);
+        let (_, stats) = run_mode(&mut net, book, mode, Budget::default()).unwrap();
+        let rules = stats.anni + stats.comm + stats.eras + stats.copy + stats.oper + stats.coll + stats.link + stats.void;
+        assert_eq!(rules, net.rwts, "{}", mode);
+        assert!(stats.anni > 0 && stats.comm > 0 && stats.coll == 1, "{}: {:?}", mode, stats);
+        assert!(stats.dref >= 4 && stats.peak >= net.used && stats.rnds > 0, "{}: {:?}", mode, stats);
+    }
+}
+
+#[test]
+fn definition_profile() {
+    // Every expansion and every rewrite of a net loaded from definitions is charged to one of them.
+    let book = &mut tree_book();
+    let main = define(book, "main", "$ r & @c12 ~ (0 @g_s (0 @g_z r)) & @c12 ~ *");
+    for mode in ["seq", "dfs"] {
+        let mut net = CoreNet::new(1 << 18);
+        net.boot(main);
+        net.profile();
+        let (_, stats) = run_mode(&mut net, book, mode, Budget::default()).unwrap();
+        let prof = net.prof.as_ref().unwrap();
+        let costs = prof.defs.values();
+        assert_eq!(costs.clone().map(|cost| cost.dref).sum::<usize>(), stats.dref);
+        assert_eq!(costs.clone().map(|cost| cost.rwts).sum::<usize>(), net.rwts);
+        for (id, cost) in &prof.defs {
+            let def = book.get(*id).unwrap();
+            assert_eq!((cost.node, cost.acts), (cost.dref * def.node.len(), cost.dref * def.acts.len()));
+        }
+        assert_eq!(prof.defs[&book.syms.id("c12").unwrap()].dref, 1);
+        assert_eq!(prof.to_string().lines().count(), 4);
+        assert!(prof.show(&book.syms).contains("@c12: dref 1 node 25 acts 0 rwts "), "{}", prof);
+    }
+}
+
+#[test]
+fn reduction_observer() {
+    // An observer sees every rewrite, expansion and round, agreeing with the stats of the net.
+    #[derive(Default)]
+    struct Trace {
+        stats: Stats,
+        alloc: usize,
+        freed: usize,
+        drefs: usize,
+        links: usize,
+        rnds: usize,
+    }
+    impl ReductionObserver for Trace {
+        fn rewrite(&mut self, rw: &Rewrite) {
+            self.stats.count(rw.rule);
+            self.alloc += rw.alloc;
+            self.freed += rw.freed;
+        }
+        fn deref(&mut self, _id: Val, _node: usize) {
+            self.drefs += 1;
+        }
+        fn link(&mut self, _a: Ptr, _b: Ptr) {
+            self.links += 1;
+        }
+        fn round(&mut self, rnds: usize, _acts: usize) {
+            self.rnds = rnds;
+        }
+    }
+    let book = &mut tree_book();
+    let main = define(book, "main", "$ r & @c12 ~ (0 @g_s (0 @g_z r)) & 3 ~ <[+4] *>");
+    let mut net = CoreNet::new(1 << 18);
+    net.boot(main);
+    let work = &mut Worker::with_observer(book, Trace::default());
+    let (_, stats) = net.normal_in(book, work, Budget::default()).unwrap();
+    let main_size = book.get(main).unwrap().node.len();
+    let trace = &work.obs;
+    assert_eq!(trace.stats, Stats { dref: 0, peak: 0, rnds: 0, ..stats });
+    assert_eq!(trace.alloc + main_size, trace.freed + net.used);
+    assert_eq!((trace.drefs, trace.rnds), (stats.dref, stats.rnds));
+    assert!(trace.links >= net.rwts - stats.link);
+}
+
+#[test]
+fn symbol_table() {
+    // Names of any length get dense ids, and are kept for readback.
+    let book = &mut Book::new();
+    let names = ["decO", "decI", "runO", "brnZ", ".x", "..x", "λf", "a_very_long_definition_name"];
+    for name in names {
+        define(book, name, "$ (0 x x)");
+    }
+    let main = define(book, "main", "$ (0 @decO (0 @decI (0 @.x (0 @..x (0 @λf @undefined)))))");
+    assert_eq!(names.map(|name| book.syms.id(name)), [0, 1, 2, 3, 4, 5, 6, 7].map(Some));
+    assert_eq!((main, book.syms.id("undefined")), (8, Some(9)));
+    assert_eq!(book.syms.name(4), Some(".x"));
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    net.expand(book, Ptr::new(VRR, 0)).unwrap();
+    assert_eq!(show_net_with(&net, &book.syms), "$ (0 @decO (0 @decI (0 @.x (0 @..x (0 @λf @undefined)))))\n");
+    assert_eq!(show_net(&net), "$ (0 @#0 (0 @#1 (0 @#4 (0 @#5 (0 @#6 @#9)))))\n");
+    let copy = define(book, "copy", &show_net(&net));
+    assert_eq!(book.get(copy), book.get(main));
+
+    // Binding a name to another id, or an id to another name, is a collision.
+    assert_eq!(book.syms.bind("decO", 0), Ok(()));
+    assert_eq!(book.syms.bind("decO", 1), Err(SymbolError::Collision { name: "decO".to_string(), id: 1 }));
+    assert_eq!(book.syms.bind("other", 0), Err(SymbolError::Collision { name: "other".to_string(), id: 0 }));
+    assert_eq!(book.syms.bind("other", 20), Ok(()));
+    assert_eq!(book.syms.intern("next"), Ok(21));
+    assert_eq!(book.syms.intern("no spaces"), Err(SymbolError::Invalid { name: "no spaces".to_string() }));
+    assert_eq!(book.syms.intern(""), Err(SymbolError::Invalid { name: "".to_string() }));
+    assert_eq!(book.syms.bind("far", VAL_MASK + 1), Err(SymbolError::Full));
+
+    // Names that can't be interned are errors of the code that has them.
+    let invalid = |name: &str| Err(ParseError::BadSymbol(SymbolError::Invalid { name: name.to_string() }));
+    assert_eq!(try_define(book, "no spaces", "$ *"), invalid("no spaces"));
+    assert_eq!(try_define(book, "empty", "$ (0 * @)"), invalid(""));
+    assert!(matches!(try_define(book, "huge", &format!("$ @#{}0", VAL_MASK)), Err(ParseError::BigNumber { .. })));
+}
+
+#[test]
+fn book_storage() {
+    // Definitions are indexed by id, which may leave gaps, and iterate in order of id.
+    let book = &mut Book::new();
+    let id = define(book, "id", "$ (0 x x)");
+    book.syms.bind("far", 6).unwrap();
+    let far = define(book, "far", "$ (0 a b) & @id ~ (0 a b)");
+    assert_eq!((id, far, book.defs.len()), (0, 6, 7));
+    assert_eq!(book.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![id, far]);
+    assert_eq!(book.get(3), None);
+    assert_eq!(book.get(far).map(|def| (def.node.len(), def.acts.len())), Some((2, 1)));
+    assert_eq!(book.largest(), 2);
+
+    // Expanding a REF loads the same net that was defined, and a gap is a missing definition.
+    book.syms.bind("gap", 3).unwrap();
+    let main = define(book, "main", "$ r & @gap ~ (0 @far r)");
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    assert_eq!(net.normal(book, Budget::default()), Err(RuntimeError::MissingDef { id: 3 }));
+    let main = define(book, "main", "$ @far");
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(main);
+    net.normal(book, Budget::default()).unwrap();
+    assert_eq!(show_net(&net), "$ (0 b b)\n");
+    assert!(book.undef(far).is_some());
+    assert_eq!((book.undef(far), book.get(far), book.iter().count()), (None, None, 2));
+}
+
+#[test]
+fn inline_small_definitions() {
+    // Decrements a bit string of 8 ones until it is zero, as on main.rs.
+    let book = &mut Book::new();
+    define(book, "c8", "$ (0 (1 (1 (1 (1 (1 (1 (1 (0 h g) (0 g f)) (0 f e)) (0 e d)) (0 d c)) (0 c b)) (0 b a)) (0 a R)) (0 h R))");
+    define(book, "O", "$ (0 xs (0 (0 xs r) (0 * (0 * r))))");
+    define(book, "I", "$ (0 xs (0 * (0 (0 xs r) (0 * r))))");
+    define(book, "E", "$ (0 * (0 * (0 e e)))");
+    define(book, "decO", "$ (0 p idecp) & @I ~ (0 decp idecp) & @dec ~ (0 p decp)");
+    define(book, "decI", "$ (0 p lowp) & @low ~ (0 p lowp)");
+    define(book, "dec", "$ (0 (0 @decO (0 @decI (0 @E ret))) ret)");
+    define(book, "lowO", "$ (0 p oop) & @O ~ (0 p op) & @O ~ (0 op oop)");
+    define(book, "lowI", "$ (0 p oip) & @I ~ (0 p ip) & @O ~ (0 ip oip)");
+    define(book, "low", "$ (0 (0 @lowO (0 @lowI (0 @E ret))) ret)");
+    define(book, "runO", "$ (0 p ret) & @run ~ (0 decop ret) & @dec ~ (0 op decop) & @O ~ (0 p op)");
+    define(book, "runI", "$ (0 p ret) & @run ~ (0 decip ret) & @dec ~ (0 ip decip) & @I ~ (0 p ip)");
+    define(book, "run", "$ (0 (0 @runO (0 @runI (0 @E ret))) ret)");
+    define(book, "alias", "$ @I");
+    let main = define(book, "main", "$ main & @run ~ (0 nie main) & @c8 ~ (0 @alias (0 @E nie))");
+    let run = |book: &Book| {
+        let mut net = CoreNet::new(1 << 16);
+        net.boot(main);
+        let (_, stats) = net.normal(book, Budget::default()).unwrap();
+        let mut par = CoreNet::new(1 << 16);
+        par.boot(main);
+        par.normal_par(book, Budget::default(), 4).unwrap();
+        assert_eq!(show_net(&par), show_net(&net));
+        (show_net(&net), net.rwts, stats.dref)
+    };
+    let (before, rwts, dref) = run(book);
+
+    // Inlining REFs that are expanded as soon as their callers are does the same rewrites, with
+    // fewer expansions. REFs on nodes are left alone, and so are those within a recursive cycle.
+    let old = book.defs.clone();
+    let done = book.inline(8);
+    let (after, again, fewer) = run(book);
+    assert_eq!((after, again), (before, rwts));
+    assert!(done > 0 && fewer < dref, "{} {} {}", done, fewer, dref);
+    assert_eq!(book.check(), Ok(()));
+    let id = |name: &str| book.syms.id(name).unwrap();
+    for name in ["c8", "O", "E", "dec", "low", "run"] {
+        assert_eq!(book.get(id(name)), old[id(name) as usize].as_ref(), "{}", name);
+    }
+    let acts = &book.get(id("decO")).unwrap().acts;
+    assert_eq!(acts.iter().map(|&(a, _)| a.tag() == REF).collect::<Vec<_>>(), vec![false, true]);
+    assert_eq!(acts[1].0, Ptr::new(REF, id("dec")));
+    assert_eq!(book.get(id("alias")).unwrap().root.tag(), CTR);
+    assert_eq!(book.inline(0), 0);
+
+    // Recursive definitions, such as cycles of aliases, aren't inlined, even into other callers.
+    let a = define(book, "a", "$ @b");
+    define(book, "b", "$ @a");
+    let c = define(book, "c", "$ @a");
+    let spin = define(book, "spin", "$ (0 x @spin)");
+    let d = define(book, "d", "$ @spin");
+    assert_eq!(book.inline(8), 0);
+    assert_eq!(book.get(c).unwrap().root, Ptr::new(REF, a));
+    assert_eq!(book.get(d).unwrap().root, Ptr::new(REF, spin));
+
+    // A net whose root is a variable isn't inlined facing a REF, which would be left on one of its
+    // nodes unexpanded, and then erased without the rewrites that erasing its net takes.
+    let book = &mut Book::new();
+    define(book, "hold", "$ r & (0 x x) ~ (0 r *)");
+    define(book, "val", "$ (0 * *)");
+    let main = define(book, "main", "$ (0 a a) & @hold ~ @val");
+    let run = |book: &Book| {
+        let mut net = CoreNet::new(1 << 12);
+        net.boot(main);
+        net.normal(book, Budget::default()).unwrap();
+        (show_net(&net), net.rwts)
+    };
+    let before = run(book);
+    assert_eq!(book.inline(8), 1);
+    assert_eq!(book.get(main).unwrap().acts[0].0, Ptr::new(REF, book.syms.id("hold").unwrap()));
+    assert_eq!(run(book), before);
+}
+
+#[test]
+fn precompute_definitions() {
+    let book = &mut Book::new();
+    define(book, "c2", "$ (0 (1 (0 b a) (0 a R)) (0 b R))");
+    define(book, "k2", "$ (0 (2 (0 b a) (0 a R)) (0 b R))");
+    define(book, "c4", "$ (0 (1 (1 (1 (0 d c) (0 c b)) (0 b a)) (0 a R)) (0 d R))");
+    define(book, "g_s", "$ (0 (2 r0 r1) (0 (0 r0 (0 r1 r)) r))");
+    define(book, "g_z", "$ (0 x x)");
+    define(book, "O", "$ (0 xs (0 (0 xs r) (0 * (0 * r))))");
+    define(book, "I", "$ (0 xs (0 * (0 (0 xs r) (0 * r))))");
+    define(book, "E", "$ (0 * (0 * (0 e e)))");
+    define(book, "lowO", "$ (0 p oop) & @O ~ (0 p op) & @O ~ (0 op oop)");
+    define(book, "decO", "$ (0 p idecp) & @I ~ (0 decp idecp) & @dec ~ (0 p decp)");
+    define(book, "dec", "$ (0 (0 @decO (0 @I (0 @E ret))) ret)");
+    define(book, "ex0", "$ root & @c2 ~ (0 @k2 root)");
+    define(book, "huge", "$ root & @c4 ~ (0 @c4 (0 @g_s (0 @g_z root)))");
+    define(book, "main", "$ (0 ex0 lowO) & @ex0 ~ (0 @g_z ex0) & @lowO ~ (0 * lowO)");
+    let id = |book: &Book, name: &str| book.syms.id(name).unwrap();
+    let run = |book: &Book| {
+        let mut net = CoreNet::new(1 << 16);
+        net.boot(id(book, "main"));
+        net.normal(book, Budget::default()).unwrap();
+        (show_net(&net), net.rwts)
+    };
+    let (before, rwts) = run(book);
+    let old = book.defs.clone();
+
+    // Redexes are reduced ahead of time, here those of ex0, lowO, decO and main, and so are not
+    // done again on each expansion.
+    assert_eq!(book.precompute(), 4);
+    let (after, fewer) = run(book);
+    assert_eq!(after, before);
+    assert!(fewer < rwts, "{} {}", fewer, rwts);
+    assert_eq!(book.check(), Ok(()));
+    let mut net = CoreNet::new(1 << 12);
+    net.boot(id(book, "ex0"));
+    net.expand(book, Ptr::new(VRR, 0)).unwrap();
+    assert_eq!(show_net(&net), "$ (0 (2 (0 (2 b c) d) (0 d (2 c e))) (0 b e))\n");
+    assert!(book.get(id(book, "lowO")).unwrap().acts.is_empty());
+
+    // Redexes with a REF to a recursive definition are kept, and so are definitions whose redexes
+    // take too long or grow too big.
+    let acts = &book.get(id(book, "decO")).unwrap().acts;
+    assert_eq!((acts.len(), acts[0].0), (1, Ptr::new(REF, id(book, "dec"))));
+    for name in ["dec", "huge", "c2"] {
+        assert_eq!(book.get(id(book, name)), old[id(book, name) as usize].as_ref(), "{}", name);
+    }
+    assert_eq!(book.precompute(), 0);
+}
+
+#[test]
+fn pointer_width() {
+    // Numbers and heaps are as wide as the values of pointers allow.
+    let book = &mut Book::new();
+    let main = define(book, "main", &format!("$ r & {} ~ <[+1] <[+2] r>>", NUM_MASK));
+    let mut net = CoreNet::new(usize::MAX);
+    assert_eq!(net.limit, VAL_MASK as usize + 1);
+    net.boot(main);
+    net.normal(book, Budget::default()).unwrap();
+    assert_eq!(show_net(&net), "$ 2\n");
+    assert_eq!(NUM_BITS, if cfg!(feature = "ptr64") { 52 } else { 24 });
+}
+
+#[test]
+fn constructor_labels() {
+    // Labels are stored on nodes, so there are as many as values of a pointer, and nets keep the
+    // whole heap. Larger labels are a parse error.
+    let top = Lab::MAX;
+    let code = format!("$ ({} * *)", top as u128 + 1);
+    assert!(matches!(do_parse_lnet(&code), Err(ParseError::BigNumber { .. })));
+    assert_eq!(CoreNet::new(usize::MAX).limit, VAL_MASK as usize + 1);
+
+    // Equal labels annihilate, while different ones commute.
+    let book = &mut Book::new();
+    let cases = [
+        (format!("$ (0 a b) & ({top} 1 2) ~ ({top} a b)"), "(0 1 2)".to_string()),
+        (format!("$ (0 a b) & ({top} 1 2) ~ ({} a b)", top - 1), format!("(0 ({top} 1 2) ({top} 1 2))")),
+        ("$ (0 a b) & (65537 1 (65536 2 3)) ~ (65537 a (65536 b *))".to_string(), "(0 1 2)".to_string()),
+        ("$ (0 a b) & (65537 1 2) ~ (1 a b)".to_string(), "(0 (65537 1 2) (65537 1 2))".to_string()),
+    ];
+    for (code, result) in cases {
+        let main = define(book, "main", &code);
+        for mode in ["seq", "par", "dfs"] {
+            let mut net = CoreNet::new(1 << 12);
+            net.boot(main);
+            run_mode(&mut net, book, mode, Budget::default()).unwrap();
+            assert_eq!(show_net(&net), format!("$ {}\n", result), "{} ({})", code, mode);
+        }
+    }
+}
+
+#[test]
+fn numeric_match() {
+    let book = &mut Book::new();
+    define(book, "sum", "$ (0 ?<(0 0 @sumS) r> r)");
+    define(book, "sumS", "$ (0 (1 a <[+1] <[+] <x r>>>) r) & @sum ~ (0 a x)");
+    let cases = [
+        ("$ r & 0 ~ ?<(0 100 (0 <[*2] o> o)) r>", "100"),
+        ("$ r & 5 ~ ?<(0 100 (0 <[*2] o> o)) r>", "8"),
+        ("$ r & 1 ~ ?<(0 a (0 p (0 a p))) r>", "(0 * 0)"),
+        ("$ r & @sum ~ (0 100 r)", "5050"),
+        ("$ r & * ~ ?<(0 1 2) r>", "*"),
+    ];
+    for (code, result) in cases {
+        let main = define(book, "main", code);
+        for mode in ["seq", "par", "dfs"] {
+            let mut net = CoreNet::new(1 << 12);
+            net.boot(main);
+            run_mode(&mut net, book, mode, Budget::default()).unwrap();
+            assert_eq!(show_net(&net), format!("$ {}\n", result), "{} ({})", code, mode);
+        }
+    }
 }
 
 #[ignore]
@@ -20,12 +893,12 @@ fn prop_confluence(Net(unchanged): Net) -> bool {
     use rand::thread_rng;
 
     let mut reduced = unchanged.clone();
-    reduced.normal(&Book::new(), Some(MAX_STEPS));
+    reduced.normal(&Book::new(), Budget::steps(MAX_STEPS)).unwrap();
 
     for _ in 0..20 {
         let mut net = unchanged.clone();
         net.acts.shuffle(&mut thread_rng());
-        net.normal(&Book::new(), Some(MAX_STEPS));
+        net.normal(&Book::new(), Budget::steps(MAX_STEPS)).unwrap();
         if net.rwts != reduced.rwts {
             println!("rwts: {} != {}", net.rwts, reduced.rwts);
             return false;